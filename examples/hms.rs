@@ -6,6 +6,6 @@ fn main() {
     scribe.load_cognates("texts/hms.yml").unwrap();
     for _i in 0..4 {
         println!("{}", scribe.gen("root").unwrap());
-        println!("");
+        println!();
     }
 }
@@ -6,12 +6,34 @@ use serde_yaml;
 
 use crate::error::AnnalsError;
 use crate::group::Group;
+use crate::selection::Selection;
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Get the namespace of a Cognate name, the part before the last `::`.
+pub(crate) fn namespace_of(name: &str) -> &str {
+    match name.rfind("::") {
+        Some(index) => &name[..index],
+        None => "",
+    }
+}
 
 /// Named collection of [`Group`](../group/struct.Group.html)s of
 /// [`Rule`](../rule/struct.Rule.html)s.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Cognate {
     pub name: String,
+    /// How Rules are chosen from this Cognate, overriding the Scribe's
+    /// [`Selection`](../selection/enum.Selection.html) if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection: Option<Selection>,
+    /// Whether loading this Cognate into a Scribe which already has one of
+    /// the same name adds to the existing Cognate's Groups, whatever the
+    /// Scribe's [`MergePolicy`](../merge/enum.MergePolicy.html).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub extend: bool,
     groups: Vec<Group>,
 }
 
@@ -32,10 +54,25 @@ impl Cognate {
     {
         Cognate {
             name: name.into(),
+            selection: None,
+            extend: false,
             groups: vec![],
         }
     }
 
+    /// Get the namespace of this Cognate, the part of its name before the
+    /// last `::`, or an empty string if its name is unqualified.
+    ///
+    /// ```
+    /// use annals::cognate::Cognate;
+    ///
+    /// assert_eq!(Cognate::new("navy::ships::class").namespace(), "navy::ships");
+    /// assert_eq!(Cognate::new("class").namespace(), "");
+    /// ```
+    pub fn namespace(&self) -> &str {
+        namespace_of(&self.name)
+    }
+
     /// Get the number of Groups in the Cognate.
     pub fn len(&self) -> usize {
         self.groups.len()
@@ -52,7 +89,7 @@ impl Cognate {
     }
 
     /// Iterate over `Group`s in this `Cognate`.
-    pub fn iter_groups(&self) -> Iter<Group> {
+    pub fn iter_groups(&self) -> Iter<'_, Group> {
         self.groups.iter()
     }
 
@@ -76,7 +113,7 @@ impl Cognate {
     ///
     /// # Arguments
     /// * `rules` - Slice of `String` or `&str` which will be parsed as rules and
-    /// inserted into a new `Group`.
+    ///   inserted into a new `Group`.
     ///
     /// ```
     /// use annals::{cognate::Cognate, rule::Rule};
@@ -116,4 +153,13 @@ impl Cognate {
         self.groups.push(Group::new());
         self.groups.last_mut()
     }
+
+    /// Move the Groups of another Cognate into this one, after its own. This
+    /// Cognate's selection mode is kept if it has one.
+    pub fn append(&mut self, other: Cognate) {
+        self.groups.extend(other.groups);
+        if self.selection.is_none() {
+            self.selection = other.selection;
+        }
+    }
 }
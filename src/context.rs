@@ -1,12 +1,19 @@
 use std::collections::{HashMap, VecDeque};
 
 use crate::group::Group;
+use crate::inflect;
+use crate::trace::Tracer;
 
 #[derive(Clone, Debug, Default)]
 pub struct Context {
     pub tags: HashMap<String, String>,
     bindings: HashMap<String, String>,
     unpop: VecDeque<Vec<String>>,
+    trace: Option<Tracer>,
+    plurals: HashMap<String, String>,
+    depth: usize,
+    expansions: usize,
+    namespace: String,
 }
 
 impl Context {
@@ -16,6 +23,11 @@ impl Context {
             tags,
             bindings,
             unpop: VecDeque::default(),
+            trace: None,
+            plurals: HashMap::default(),
+            depth: 0,
+            expansions: 0,
+            namespace: String::new(),
         }
     }
 
@@ -25,6 +37,11 @@ impl Context {
             tags,
             bindings: HashMap::default(),
             unpop: VecDeque::default(),
+            trace: None,
+            plurals: HashMap::default(),
+            depth: 0,
+            expansions: 0,
+            namespace: String::new(),
         }
     }
 
@@ -34,15 +51,20 @@ impl Context {
             tags: HashMap::default(),
             bindings,
             unpop: VecDeque::default(),
+            trace: None,
+            plurals: HashMap::default(),
+            depth: 0,
+            expansions: 0,
+            namespace: String::new(),
         }
     }
 
-    ///
+    /// Open a new scope for sticky bindings.
     pub fn descend(&mut self) {
         self.unpop.push_back(vec![]);
     }
 
-    ///
+    /// Close the innermost binding scope, dropping any bindings made within it.
     pub fn ascend(&mut self) {
         if let Some(vec) = self.unpop.pop_back() {
             for name in vec {
@@ -51,13 +73,28 @@ impl Context {
         }
     }
 
-    /// Merge tags from a Group into this context.
+    /// Merge tags from a Group into this context. Only tags which name a
+    /// single value are merged; predicates such as `!old` or `>=200` are not.
     pub fn merge_from_group(&mut self, group: &Group) {
-        for (key, value) in &group.tags {
-            self.tags.insert(key.to_string(), value.to_string());
+        for (key, predicate) in group.predicates() {
+            if let Some(value) = predicate.value() {
+                self.tags.insert(key.to_string(), value.to_string());
+            }
         }
     }
 
+    /// Replace this Context's tags with `saved`, except for the tags named in
+    /// `exported`, which keep their current values.
+    pub(crate) fn restore_tags(&mut self, mut saved: HashMap<String, String>, exported: &[String]) {
+        for key in exported {
+            match self.tags.remove(key) {
+                Some(value) => saved.insert(key.clone(), value),
+                None => saved.remove(key),
+            };
+        }
+        self.tags = saved;
+    }
+
     /// Set the value of a tag.
     pub fn set<T: AsRef<str>>(&mut self, key: T, value: T) {
         self.tags
@@ -73,6 +110,23 @@ impl Context {
         }
     }
 
+    /// Add a binding outside of any scope, returning the value it replaces so
+    /// that it can later be passed to `restore`.
+    pub(crate) fn shadow<T: AsRef<str>>(&mut self, key: T, value: T) -> Option<String> {
+        self.bindings
+            .insert(key.as_ref().to_string(), value.as_ref().to_string())
+    }
+
+    /// Restore a binding replaced by `shadow`.
+    pub(crate) fn restore<T: AsRef<str>>(&mut self, key: T, previous: Option<String>) {
+        match previous {
+            Some(value) => {
+                self.bindings.insert(key.as_ref().to_string(), value);
+            }
+            None => self.unbind(key),
+        }
+    }
+
     /// Remove a binding.
     pub fn unbind<T: AsRef<str>>(&mut self, key: T) {
         self.bindings.remove(key.as_ref());
@@ -83,29 +137,90 @@ impl Context {
         self.bindings.get(key).cloned()
     }
 
+    /// Record the start of a Cognate expansion, returning the new nesting
+    /// depth and the number of expansions made so far.
+    pub(crate) fn enter(&mut self) -> (usize, usize) {
+        self.depth += 1;
+        (self.depth, self.count_expansion())
+    }
+
+    /// Record an expansion which does not nest, such as one item of a
+    /// repetition, returning the number of expansions made so far.
+    pub(crate) fn count_expansion(&mut self) -> usize {
+        self.expansions += 1;
+        self.expansions
+    }
+
+    /// Record the end of a Cognate expansion.
+    pub(crate) fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// Get the namespace of the Cognate being expanded, in which names are
+    /// looked up first.
+    pub(crate) fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Set the namespace of the Cognate being expanded, returning the one it
+    /// replaces so that it can be restored afterwards.
+    pub(crate) fn set_namespace(&mut self, namespace: String) -> String {
+        std::mem::replace(&mut self.namespace, namespace)
+    }
+
+    /// Record the plural form given by a Rule for the text it produced.
+    pub(crate) fn add_plural(&mut self, single: &str, plural: &str) {
+        self.plurals
+            .insert(single.to_lowercase(), plural.to_string());
+    }
+
+    /// Get the plural form recorded for a piece of text, if any, ignoring
+    /// case and giving it the same case as the text.
+    pub(crate) fn plural_of(&self, single: &str) -> Option<String> {
+        self.plurals
+            .get(&single.to_lowercase())
+            .map(|plural| inflect::match_case(single, plural))
+    }
+
+    /// Start recording a derivation tree for subsequent expansions.
+    pub(crate) fn start_trace(&mut self) {
+        self.trace = Some(Tracer::default());
+    }
+
+    /// Get the derivation tree recorder, if tracing.
+    pub(crate) fn tracer(&mut self) -> Option<&mut Tracer> {
+        self.trace.as_mut()
+    }
+
     /// Check if the Context currently holds any tags.
     pub fn has_tags(&self) -> bool {
         !self.tags.is_empty()
     }
 
-    /// Check if a group's tags match the tags in this Context exactly.
+    /// Check if the tags in this Context satisfy a group's tags, each of
+    /// which is read as a [`Predicate`](../predicate/enum.Predicate.html).
     pub fn accept_strict(&self, group: &Group) -> bool {
-        !self
-            .tags
+        group
+            .predicates()
             .iter()
-            .filter(|(ref key, _val)| group.tags.contains_key(*key))
-            .any(|(ref key, val)| group.tags[*key] != **val)
+            .all(|(key, predicate)| predicate.matches(self.tags.get(key).map(String::as_str)))
     }
 
-    /// Check if a group's tags match the tags in this Context.
-    pub fn accept(&self, group: &Group) -> bool {
-        if self.tags.is_empty() || group.tags.is_empty() {
-            return true;
-        }
-        !self
-            .tags
+    /// Count the tags of a group which this Context holds and satisfies.
+    pub fn salience(&self, group: &Group) -> usize {
+        group
+            .predicates()
             .iter()
-            .filter(|(ref key, _val)| group.tags.contains_key(*key))
-            .any(|(ref key, val)| group.tags[*key] != **val)
+            .filter(|(key, predicate)| match self.tags.get(key) {
+                Some(tag) => predicate.matches(Some(tag)),
+                None => false,
+            })
+            .count()
+    }
+
+    /// Check if the tags in this Context satisfy a group's tags. Equivalent
+    /// to `accept_strict`.
+    pub fn accept(&self, group: &Group) -> bool {
+        self.accept_strict(group)
     }
 }
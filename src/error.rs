@@ -2,6 +2,8 @@ use serde::de;
 use std::error::Error;
 use std::fmt::{self, Display};
 
+use crate::cognate::Cognate;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     EmptyRule,
@@ -9,6 +11,7 @@ pub enum ParseError {
     InvalidExpression(usize, usize),
     InvalidName(usize, usize),
     InvalidRange(usize, usize),
+    InvalidWeight(usize, usize),
     UnbalancedBrackets,
     UnknownCommand(usize, usize),
     ZeroLengthSubst(usize, usize),
@@ -27,6 +30,9 @@ impl fmt::Display for ParseError {
             ParseError::InvalidRange(beg, end) => {
                 write!(f, "Invalid range specification ({}, {})", beg, end)
             }
+            ParseError::InvalidWeight(beg, end) => {
+                write!(f, "Invalid weight specification ({}, {})", beg, end)
+            }
             ParseError::ZeroLengthSubst(beg, end) => {
                 write!(f, "Zero-length substitution expression ({}, {})", beg, end)
             }
@@ -38,15 +44,62 @@ impl fmt::Display for ParseError {
 
 #[derive(Debug)]
 pub enum AnnalsError {
-    UnknownCognate { name: String },
-    EmptyCognate { name: String },
-    NoSuitableGroups { name: String, context: String },
-    UnknownToken { content: String },
-    UnboundVariable { name: String },
-    SerdeError { msg: String },
-    InvalidRule { err: ParseError, expr: String },
+    UnknownCognate {
+        name: String,
+    },
+    DuplicateCognate {
+        name: String,
+    },
+    EmptyCognate {
+        name: String,
+    },
+    NoSuitableGroups {
+        name: String,
+        context: String,
+    },
+    UnknownToken {
+        content: String,
+    },
+    UnboundVariable {
+        name: String,
+    },
+    UnknownCommand {
+        name: String,
+    },
+    DepthLimitExceeded {
+        name: String,
+        limit: usize,
+    },
+    ExpansionLimitExceeded {
+        limit: usize,
+    },
+    OutputLimitExceeded {
+        limit: usize,
+    },
+    SerdeError {
+        msg: String,
+    },
+    InvalidRule {
+        err: ParseError,
+        expr: String,
+    },
+    InvalidInclude {
+        pattern: String,
+    },
+    InvalidNamespace {
+        name: String,
+    },
+    FormatDisabled {
+        feature: String,
+    },
+    InFile {
+        path: String,
+        err: Box<AnnalsError>,
+    },
     IOError(std::io::Error),
     YAMLError(serde_yaml::Error),
+    #[cfg(feature = "json")]
+    JSONError(serde_json::Error),
     UnknownError,
 }
 
@@ -54,6 +107,12 @@ impl AnnalsError {
     pub fn from_invalid_rule(expr: String, err: ParseError) -> Self {
         AnnalsError::InvalidRule { err, expr }
     }
+
+    pub(crate) fn duplicate(cognate: &Cognate) -> Self {
+        AnnalsError::DuplicateCognate {
+            name: cognate.name.clone(),
+        }
+    }
 }
 
 impl std::convert::From<std::io::Error> for AnnalsError {
@@ -67,6 +126,13 @@ impl std::convert::From<serde_yaml::Error> for AnnalsError {
     }
 }
 
+#[cfg(feature = "json")]
+impl std::convert::From<serde_json::Error> for AnnalsError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::JSONError(err)
+    }
+}
+
 /// Handler for transforming de::Error.
 impl de::Error for AnnalsError {
     fn custom<T: Display>(msg: T) -> Self {
@@ -81,17 +147,38 @@ impl Display for AnnalsError {
         use AnnalsError::*;
         match self {
             UnknownCognate { name } => write!(f, "Unknown cognate: {}", name),
+            DuplicateCognate { name } => write!(f, "Duplicate cognate: {}", name),
             EmptyCognate { name } => write!(f, "No groups in cognate: {}", name),
             NoSuitableGroups { name, context } => {
                 write!(f, "No suitable groups for {} in context: {}", name, context)
             }
             UnknownToken { content } => write!(f, "Unknown token: {}", content),
             UnboundVariable { name } => write!(f, "Unbound variable: {}", name),
+            UnknownCommand { name } => write!(f, "Unknown command: {}", name),
+            DepthLimitExceeded { name, limit } => write!(
+                f,
+                "Nesting depth limit of {} exceeded while expanding {}",
+                limit, name
+            ),
+            ExpansionLimitExceeded { limit } => {
+                write!(f, "Expansion limit of {} exceeded", limit)
+            }
+            OutputLimitExceeded { limit } => {
+                write!(f, "Output length limit of {} bytes exceeded", limit)
+            }
             SerdeError { msg } => write!(f, "{}", msg),
             InvalidRule { err, expr } => format_invalid_rule(f, err, expr),
+            InvalidInclude { pattern } => write!(f, "Invalid include: {}", pattern.trim()),
+            InvalidNamespace { name } => write!(f, "Invalid namespace: {}", name.trim()),
+            FormatDisabled { feature } => {
+                write!(f, "Grammar format requires the `{}` feature", feature)
+            }
+            InFile { path, err } => write!(f, "{}: {}", path, err),
             UnknownError => write!(f, "Unknown error"),
             IOError(err) => write!(f, "{}", err),
             YAMLError(err) => write!(f, "{}", err),
+            #[cfg(feature = "json")]
+            JSONError(err) => write!(f, "{}", err),
         }
     }
 }
@@ -114,6 +201,7 @@ fn format_invalid_rule(f: &mut fmt::Formatter, err: &ParseError, expr: &str) ->
         ParseError::InvalidExpression(beg, _end)
         | ParseError::InvalidName(beg, _end)
         | ParseError::InvalidRange(beg, _end)
+        | ParseError::InvalidWeight(beg, _end)
         | ParseError::UnknownCommand(beg, _end)
         | ParseError::ZeroLengthSubst(beg, _end) => {
             writeln!(f, "{}", expr)?;
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AnnalsError;
+
+/// A file format in which grammars can be loaded and saved.
+///
+/// YAML is always available. JSON is enabled by the default `json` feature,
+/// which also provides the [`tracery`](../tracery/index.html) conversions,
+/// while TOML and RON require the `toml` and `ron` features respectively;
+/// using a format whose feature is disabled fails with
+/// `AnnalsError::FormatDisabled`.
+///
+/// TOML documents must be tables, so a list of Cognates is written as an
+/// array of tables named `cognate`:
+///
+/// ```toml
+/// [[cognate]]
+/// name = "animal"
+///
+/// [[cognate.groups]]
+/// rules = ["whale", "milk snake"]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Yaml,
+    Json,
+    Toml,
+    Ron,
+}
+
+/// A list of items in a TOML document, which must be a table.
+#[derive(Deserialize, Serialize)]
+struct TomlList<T> {
+    #[serde(default = "Vec::new")]
+    cognate: Vec<T>,
+}
+
+impl Format {
+    /// Get the Format used for files with an extension, if there is one.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "yml" | "yaml" => Some(Format::Yaml),
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "ron" => Some(Format::Ron),
+            _ => None,
+        }
+    }
+
+    /// Get the Format of a file from its extension. Files with any other
+    /// extension, or none, are taken to be YAML.
+    ///
+    /// ```
+    /// use annals::format::Format;
+    ///
+    /// assert_eq!(Format::from_path("texts/hms.yml"), Format::Yaml);
+    /// assert_eq!(Format::from_path("mods/kraken.toml"), Format::Toml);
+    /// assert_eq!(Format::from_path("grammar"), Format::Yaml);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Format::from_extension)
+            .unwrap_or(Format::Yaml)
+    }
+
+    /// Deserialize a value from a document in this Format.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, data: &str) -> Result<T, AnnalsError> {
+        match self {
+            Format::Yaml => serde_yaml::from_str(data).map_err(Into::into),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::from_str(data).map_err(Into::into),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::from_str(data).map_err(|err| AnnalsError::SerdeError {
+                msg: err.to_string(),
+            }),
+            #[cfg(feature = "ron")]
+            Format::Ron => ron::de::from_str(data).map_err(|err| AnnalsError::SerdeError {
+                msg: err.to_string(),
+            }),
+            #[cfg(not(feature = "json"))]
+            Format::Json => Err(AnnalsError::FormatDisabled {
+                feature: "json".to_string(),
+            }),
+            #[cfg(not(feature = "toml"))]
+            Format::Toml => Err(AnnalsError::FormatDisabled {
+                feature: "toml".to_string(),
+            }),
+            #[cfg(not(feature = "ron"))]
+            Format::Ron => Err(AnnalsError::FormatDisabled {
+                feature: "ron".to_string(),
+            }),
+        }
+    }
+
+    /// Deserialize a list from a document in this Format.
+    pub(crate) fn deserialize_list<T: DeserializeOwned>(
+        self,
+        data: &str,
+    ) -> Result<Vec<T>, AnnalsError> {
+        match self {
+            Format::Toml => self
+                .deserialize::<TomlList<T>>(data)
+                .map(|list| list.cognate),
+            _ => self.deserialize(data),
+        }
+    }
+
+    /// Serialize a value as a document in this Format.
+    pub(crate) fn serialize<T: Serialize>(self, value: &T) -> Result<String, AnnalsError> {
+        match self {
+            Format::Yaml => serde_yaml::to_string(value).map_err(Into::into),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(value).map_err(Into::into),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::to_string_pretty(value).map_err(|err| AnnalsError::SerdeError {
+                msg: err.to_string(),
+            }),
+            #[cfg(feature = "ron")]
+            Format::Ron => ron::ser::to_string_pretty(value, Default::default()).map_err(|err| {
+                AnnalsError::SerdeError {
+                    msg: err.to_string(),
+                }
+            }),
+            #[cfg(not(feature = "json"))]
+            Format::Json => Err(AnnalsError::FormatDisabled {
+                feature: "json".to_string(),
+            }),
+            #[cfg(not(feature = "toml"))]
+            Format::Toml => Err(AnnalsError::FormatDisabled {
+                feature: "toml".to_string(),
+            }),
+            #[cfg(not(feature = "ron"))]
+            Format::Ron => Err(AnnalsError::FormatDisabled {
+                feature: "ron".to_string(),
+            }),
+        }
+    }
+
+    /// Serialize a list as a document in this Format.
+    pub(crate) fn serialize_list<T: Serialize>(self, list: Vec<T>) -> Result<String, AnnalsError> {
+        match self {
+            Format::Toml => self.serialize(&TomlList { cognate: list }),
+            _ => self.serialize(&list),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cognate::Cognate;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Format::from_path("a/b.YAML"), Format::Yaml);
+        assert_eq!(Format::from_path("b.json"), Format::Json);
+        assert_eq!(Format::from_path("b.ron"), Format::Ron);
+        assert_eq!(Format::from_path("b.txt"), Format::Yaml);
+        assert_eq!(Format::from_extension("txt"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let yaml = "[{name: animal, selection: salient, groups: [\
+                    {tags: {size: big}, scope: {export: [size]}, rules: [whale, <^2>orca]}, \
+                    {weight: 0.5, bind: true, rules: [mouse]}]}]";
+        let cognates: Vec<Cognate> = Format::Yaml.deserialize_list(yaml).unwrap();
+        let expected = Format::Yaml.serialize_list(cognates).unwrap();
+        for format in [Format::Json, Format::Toml, Format::Ron].iter() {
+            let cognates: Vec<Cognate> = Format::Yaml.deserialize_list(yaml).unwrap();
+            match format.serialize_list(cognates) {
+                Ok(data) => {
+                    let cognates: Vec<Cognate> = format.deserialize_list(&data).unwrap();
+                    assert_eq!(Format::Yaml.serialize_list(cognates).unwrap(), expected);
+                }
+                Err(AnnalsError::FormatDisabled { .. }) => (),
+                Err(err) => panic!("Failed to serialize {:?}: {}", format, err),
+            }
+        }
+    }
+}
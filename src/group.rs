@@ -1,21 +1,62 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
 use crate::error::AnnalsError;
+use crate::predicate::Predicate;
 use crate::rule::{rule_list, Rule};
 
 fn always_false() -> bool {
     false
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn is_default_weight(weight: &f64) -> bool {
+    (*weight - default_weight()).abs() < f64::EPSILON
+}
+
+/// Whether the tags a Group merges into the
+/// [`Context`](../context/struct.Context.html) outlive the expansion of its Rule.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagScope {
+    /// Tags remain in the Context for the rest of the text.
+    #[default]
+    Propagate,
+    /// Tags are visible only while expanding the Rule, after which the
+    /// Context's tags are restored, including any set by nested expansions.
+    Local,
+    /// As `Local`, except that the listed tags keep the values they had at
+    /// the end of the expansion.
+    Export(Vec<String>),
+}
+
+impl TagScope {
+    fn is_propagate(&self) -> bool {
+        *self == TagScope::Propagate
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Group {
     #[serde(default)]
     note: String,
     #[serde(default = "always_false")]
     bind: bool,
+    /// Tags matched against the Context, each read as a
+    /// [`Predicate`](../predicate/enum.Predicate.html). They are parsed when
+    /// first matched, so should be changed through `set_tag` thereafter.
     #[serde(default)]
     pub tags: HashMap<String, String>,
+    #[serde(skip)]
+    predicates: OnceLock<Vec<(String, Predicate)>>,
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f64,
+    #[serde(default, skip_serializing_if = "TagScope::is_propagate")]
+    pub scope: TagScope,
     #[serde(with = "rule_list")]
     pub rules: Vec<Rule>,
 }
@@ -27,6 +68,9 @@ impl Group {
             note: String::new(),
             bind: false,
             tags: HashMap::new(),
+            predicates: OnceLock::new(),
+            weight: default_weight(),
+            scope: TagScope::Propagate,
             rules: vec![],
         }
     }
@@ -53,6 +97,9 @@ impl Group {
             note: String::new(),
             bind: false,
             tags: HashMap::new(),
+            predicates: OnceLock::new(),
+            weight: default_weight(),
+            scope: TagScope::Propagate,
             rules,
         })
     }
@@ -75,7 +122,7 @@ impl Group {
     pub fn add_rules<T: AsRef<str>>(&mut self, rules: &[T]) -> Result<(), AnnalsError> {
         let rules: Result<Vec<_>, _> = rules.iter().map(|lit| Rule::new(lit.as_ref())).collect();
         let rules = rules?;
-        self.rules.extend(rules.into_iter());
+        self.rules.extend(rules);
         Ok(())
     }
 
@@ -87,6 +134,60 @@ impl Group {
     ///
     pub fn set_tag(&mut self, key: &str, val: &str) {
         self.tags.insert(key.to_string(), val.to_string());
+        self.predicates.take();
+    }
+
+    /// Get this Group's tags, each parsed as a Predicate. Tags are parsed
+    /// once, rather than on every expansion.
+    pub(crate) fn predicates(&self) -> &[(String, Predicate)] {
+        self.predicates.get_or_init(|| {
+            self.tags
+                .iter()
+                .map(|(key, value)| (key.clone(), Predicate::parse(value)))
+                .collect()
+        })
+    }
+
+    /// Check if a result chosen from this group is bound to the name of its
+    /// Cognate, so that later references to the Cognate in the same rule
+    /// reuse it, as with `<!name>`.
+    pub fn binds(&self) -> bool {
+        self.bind
+    }
+
+    /// Set whether a result chosen from this group is bound to the name of
+    /// its Cognate.
+    ///
+    /// # Arguments
+    /// * `bind` - Whether to bind results; defaults to `false`.
+    ///
+    pub fn set_bind(&mut self, bind: bool) {
+        self.bind = bind;
+    }
+
+    /// Set the selection weight for this group. Each Rule's own weight is
+    /// multiplied by the weight of its Group.
+    ///
+    /// # Arguments
+    /// * `weight` - Non-negative weight; defaults to 1.
+    ///
+    pub fn set_weight(&mut self, weight: f64) {
+        self.weight = weight;
+    }
+
+    /// Set whether this group's tags outlive the expansion of its rules.
+    ///
+    /// # Arguments
+    /// * `scope` - Scope of the group's tags; defaults to `TagScope::Propagate`.
+    ///
+    pub fn set_scope(&mut self, scope: TagScope) {
+        self.scope = scope;
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group::new()
     }
 }
 
@@ -130,7 +231,7 @@ impl<'a> Iterator for GroupListIter<'a> {
             match self.t_iter.next() {
                 Some(template) => {
                     self.index += 1;
-                    return Some((&template, &self.groups.last().unwrap()));
+                    return Some((template, self.groups.last().unwrap()));
                 }
                 None => {
                     if !self.advance_group() {
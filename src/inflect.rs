@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+/// Irregular English plurals, in lowercase.
+const IRREGULAR: &[(&str, &str)] = &[
+    ("calf", "calves"),
+    ("child", "children"),
+    ("criterion", "criteria"),
+    ("die", "dice"),
+    ("elf", "elves"),
+    ("foot", "feet"),
+    ("fungus", "fungi"),
+    ("goose", "geese"),
+    ("half", "halves"),
+    ("knife", "knives"),
+    ("leaf", "leaves"),
+    ("life", "lives"),
+    ("loaf", "loaves"),
+    ("louse", "lice"),
+    ("man", "men"),
+    ("mouse", "mice"),
+    ("ox", "oxen"),
+    ("person", "people"),
+    ("phenomenon", "phenomena"),
+    ("self", "selves"),
+    ("shelf", "shelves"),
+    ("thief", "thieves"),
+    ("tooth", "teeth"),
+    ("wife", "wives"),
+    ("wolf", "wolves"),
+    ("woman", "women"),
+];
+
+/// Nouns whose plural is the same as the singular.
+const UNINFLECTED: &[&str] = &[
+    "aircraft",
+    "bison",
+    "cod",
+    "deer",
+    "fish",
+    "moose",
+    "offspring",
+    "salmon",
+    "series",
+    "sheep",
+    "species",
+    "swine",
+    "trout",
+];
+
+/// Nouns ending in a consonant and 'o' which take 'es' in the plural.
+const O_ES: &[&str] = &[
+    "echo", "hero", "potato", "tomato", "torpedo", "veto", "volcano",
+];
+
+/// Give `replacement` the same case as `word`: all capitals, initial
+/// capital, or unchanged.
+pub(crate) fn match_case(word: &str, replacement: &str) -> String {
+    if word.len() > 1 && word.chars().all(|ch| !ch.is_lowercase()) {
+        return replacement.to_uppercase();
+    }
+    let mut chs = word.chars();
+    match chs.next() {
+        Some(first) if first.is_uppercase() => {
+            let mut rep = replacement.chars();
+            match rep.next() {
+                Some(t) => t.to_uppercase().chain(rep).collect(),
+                None => String::new(),
+            }
+        }
+        _ => replacement.to_string(),
+    }
+}
+
+/// Pluralise a single English word.
+fn pluralize_word(word: &str) -> String {
+    if word.is_empty() {
+        return String::new();
+    }
+    let lower = word.to_lowercase();
+    if UNINFLECTED.contains(&lower.as_str()) {
+        return word.to_string();
+    }
+    if let Some((_, plural)) = IRREGULAR.iter().find(|(single, _)| *single == lower) {
+        return match_case(word, plural);
+    }
+    let shouting = word.len() > 1 && word.chars().all(|ch| !ch.is_lowercase());
+    let suffix = |s: &str| {
+        if shouting {
+            s.to_uppercase()
+        } else {
+            s.to_string()
+        }
+    };
+    let is_vowel = |ch: char| "aeiou".contains(ch);
+    let before_last = lower.chars().rev().nth(1);
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|end| lower.ends_with(end))
+    {
+        format!("{}{}", word, suffix("es"))
+    } else if lower.ends_with('y') && before_last.is_some_and(|ch| !is_vowel(ch)) {
+        format!("{}{}", &word[..word.len() - 1], suffix("ies"))
+    } else if O_ES.contains(&lower.as_str()) {
+        format!("{}{}", word, suffix("es"))
+    } else {
+        format!("{}{}", word, suffix("s"))
+    }
+}
+
+/// Pluralise an English noun phrase. The last word is pluralised, unless the
+/// phrase has the form "X of Y", in which case X is.
+pub(crate) fn pluralize(phrase: &str) -> String {
+    if let Some(index) = phrase.find(" of ") {
+        let (head, tail) = phrase.split_at(index);
+        return format!("{}{}", pluralize(head), tail);
+    }
+    let trimmed = phrase.trim_end();
+    let start = trimmed.rfind(' ').map_or(0, |index| index + 1);
+    format!(
+        "{}{}{}",
+        &trimmed[..start],
+        pluralize_word(&trimmed[start..]),
+        &phrase[trimmed.len()..]
+    )
+}
+
+/// Word beginnings whose article differs from the one suggested by their
+/// first letter, in lowercase. The longest matching prefix wins.
+const ARTICLE_EXCEPTIONS: &[(&str, &str)] = &[
+    // Silent 'h'.
+    ("heir", "an"),
+    ("honest", "an"),
+    ("honor", "an"),
+    ("honour", "an"),
+    ("hour", "an"),
+    // Vowels pronounced 'you' or 'wun'.
+    ("eu", "a"),
+    ("ewe", "a"),
+    ("once", "a"),
+    ("ubiq", "a"),
+    ("uku", "a"),
+    ("unanim", "a"),
+    ("uni", "a"),
+    ("unid", "an"),
+    ("unim", "an"),
+    ("unin", "an"),
+    ("ura", "a"),
+    ("ure", "a"),
+    ("uri", "a"),
+    ("usa", "a"),
+    ("use", "a"),
+    ("usu", "a"),
+    ("ute", "a"),
+    ("uti", "a"),
+    ("uto", "a"),
+    ("uvu", "a"),
+];
+
+/// Words whose article differs from the one suggested by their first letter,
+/// in lowercase. These match only the whole word or the first part of a
+/// compound, as in "a one-eyed cat" but "an onerous task".
+const ARTICLE_WORDS: &[(&str, &str)] = &[("one", "a")];
+
+/// Letters whose names begin with a vowel sound.
+const AN_LETTERS: &str = "aefhilmnorsx";
+
+/// Find the article for the longest prefix of `word` in `exceptions`.
+fn longest_prefix<'a, I>(word: &str, exceptions: I) -> Option<&'a str>
+where
+    I: Iterator<Item = (&'a str, &'a str)>,
+{
+    exceptions
+        .filter(|(prefix, _)| word.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, article)| article)
+}
+
+/// Choose the article for a number written in digits, by how it is read.
+fn number_article(digits: &str) -> &'static str {
+    let digits = digits
+        .chars()
+        .take_while(|ch| ch.is_ascii_digit() || *ch == ',')
+        .filter(char::is_ascii_digit)
+        .collect::<String>();
+    // 8, 80, 800...; 11 and 18, 11,000 and 18,000...
+    let eleven = (digits.starts_with("11") || digits.starts_with("18")) && digits.len() % 3 == 2;
+    if digits.starts_with('8') || eleven {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// Choose "a" or "an" for an English phrase, by the sound of its first word.
+/// `exceptions` maps lowercase word beginnings to articles, and takes
+/// precedence over the built-in rules.
+pub(crate) fn indefinite_article<'a>(
+    phrase: &str,
+    exceptions: &'a HashMap<String, String>,
+) -> &'a str {
+    let word = phrase
+        .trim_start_matches(|ch: char| !ch.is_alphanumeric())
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    let lower = word.to_lowercase();
+    let user = exceptions.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    if let Some(article) = longest_prefix(&lower, user) {
+        return article;
+    }
+    let first = match word.chars().next() {
+        Some(ch) => ch,
+        None => return "a",
+    };
+    if first.is_ascii_digit() {
+        return number_article(word);
+    }
+    // Single letters and acronyms are read letter by letter, as in "an X-ray"
+    // or "an FBI agent".
+    let head = word.split('-').next().unwrap_or(word);
+    let letters = head.chars().count() == 1
+        || (head.chars().count() > 1 && head.chars().all(|ch| ch.is_uppercase()));
+    if letters {
+        let lower_first = first.to_lowercase().next().unwrap_or(first);
+        return if AN_LETTERS.contains(lower_first) {
+            "an"
+        } else {
+            "a"
+        };
+    }
+    let stem = lower
+        .split(|ch: char| !ch.is_alphanumeric())
+        .next()
+        .unwrap_or("");
+    if let Some((_, article)) = ARTICLE_WORDS.iter().find(|(word, _)| *word == stem) {
+        return article;
+    }
+    if let Some(article) = longest_prefix(&lower, ARTICLE_EXCEPTIONS.iter().cloned()) {
+        return article;
+    }
+    if "aeiou".contains(lower.chars().next().unwrap_or(' ')) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pluralize() {
+        macro_rules! plural {
+            ($single: expr, $plural: expr) => {
+                assert_eq!(pluralize($single), $plural);
+            };
+        }
+        plural!("whale", "whales");
+        plural!("milk snake", "milk snakes");
+        plural!("mouse", "mice");
+        plural!("Mouse", "Mice");
+        plural!("sheep", "sheep");
+        plural!("fox", "foxes");
+        plural!("church", "churches");
+        plural!("glass", "glasses");
+        plural!("pony", "ponies");
+        plural!("monkey", "monkeys");
+        plural!("hero", "heroes");
+        plural!("piano", "pianos");
+        plural!("wolf", "wolves");
+        plural!("man of war", "men of war");
+        plural!("CAT", "CATS");
+        plural!("", "");
+    }
+
+    #[test]
+    fn test_indefinite_article() {
+        let none = HashMap::new();
+        macro_rules! article {
+            ($phrase: expr, $article: expr) => {
+                assert_eq!(indefinite_article($phrase, &none), $article, "{}", $phrase);
+            };
+        }
+        article!("elephant", "an");
+        article!("mouse", "a");
+        article!("hour", "an");
+        article!("honest man", "an");
+        article!("hat", "a");
+        article!("unicorn", "a");
+        article!("uninformed guess", "an");
+        article!("umbrella", "an");
+        article!("European", "a");
+        article!("one-eyed cat", "a");
+        article!("one", "a");
+        article!("onerous task", "an");
+        article!("once-great navy", "a");
+        article!("8-gun frigate", "an");
+        article!("80mm gun", "an");
+        article!("11-year-old", "an");
+        article!("110 guns", "a");
+        article!("18,000 men", "an");
+        article!("1,800 men", "a");
+        article!("FBI agent", "an");
+        article!("UFO", "a");
+        article!("X-ray", "an");
+        article!("\"open\" door", "an");
+
+        let mut exceptions = HashMap::new();
+        exceptions.insert("herb".to_string(), "an".to_string());
+        exceptions.insert("nato".to_string(), "a".to_string());
+        assert_eq!(indefinite_article("herbalist", &exceptions), "an");
+        assert_eq!(indefinite_article("NATO summit", &exceptions), "a");
+        assert_eq!(indefinite_article("NSA agent", &exceptions), "an");
+    }
+}
@@ -2,9 +2,10 @@
 extern crate lazy_static;
 // #[macro_use] extern crate serde_derive;
 
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -13,49 +14,285 @@ use titlecase::titlecase;
 pub mod cognate;
 pub mod context;
 pub mod error;
+pub mod format;
 pub mod group;
+mod inflect;
+pub mod limits;
+mod loader;
+pub mod merge;
+mod overlay;
 mod parse;
+pub mod predicate;
 pub mod rule;
+pub mod selection;
+pub mod trace;
+#[cfg(feature = "json")]
+pub mod tracery;
+pub mod transform;
+pub mod validate;
 
 pub use context::Context;
 
 use crate::cognate::Cognate;
-use crate::error::AnnalsError;
-use crate::group::GroupListIter;
+use crate::error::{AnnalsError, ParseError};
+use crate::format::Format;
+use crate::group::{Group, GroupListIter, TagScope};
+use crate::limits::Limits;
+use crate::loader::{in_file, Loader};
+use crate::merge::MergePolicy;
+use crate::overlay::{Flattened, View};
 use crate::parse::{Command, Token};
 use crate::rule::Rule;
+use crate::selection::Selection;
+use crate::trace::{Derivation, Growth};
+use crate::transform::{Transform, Transforms};
+use crate::validate::Diagnostic;
 
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Scribe {
     cognates: HashMap<String, Cognate>,
+    #[serde(skip)]
+    limits: Limits,
+    #[serde(skip)]
+    articles: HashMap<String, String>,
+    #[serde(skip)]
+    transforms: Transforms,
+    #[serde(skip)]
+    selection: Selection,
+    #[serde(skip)]
+    merge_policy: MergePolicy,
+    #[serde(skip)]
+    base: Option<Arc<Scribe>>,
+    #[serde(skip)]
+    hidden: HashSet<String>,
+    #[serde(skip)]
+    hidden_groups: HashMap<String, Vec<usize>>,
 }
 
 impl Scribe {
-    /// Create a new Scribe from a YAML file.
+    /// Create a new Scribe from a file, whose format is chosen by its
+    /// extension as described for [`Format::from_path`](format/enum.Format.html#method.from_path).
     pub fn from(path: &str) -> Result<Self, AnnalsError> {
-        let f = File::open(path)?;
-        serde_yaml::from_reader(f).map_err(Into::into)
+        let data = std::fs::read_to_string(path)?;
+        let scribe: Scribe = Format::from_path(path).deserialize(&data)?;
+        scribe
+            .iter()
+            .try_for_each(|cog| scribe.check_commands(cog))?;
+        Ok(scribe)
     }
 
-    /// Load a list of Cognates from a YAML file, inserting them into this Scribe.
-    pub fn load_cognates(&mut self, path: &str) -> Result<(), AnnalsError> {
-        let f = File::open(path)?;
-        let cogs: Vec<Cognate> = serde_yaml::from_reader(f)?;
-        for cog in cogs {
-            self.insert_cognate(cog);
+    /// Create an empty Scribe layered over `base`, which it shares rather than
+    /// copies. Cognates loaded into the overlay are added alongside those of
+    /// the base, replacing any of the same name unless they extend them, and
+    /// the base's Cognates and Groups can be hidden. The base's limits,
+    /// selection mode, merge policy, article exceptions and transforms are
+    /// copied into the overlay.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use annals::Scribe;
+    ///
+    /// let mut base = Scribe::default();
+    /// base.load_cognates_str("[{name: greeting, groups: [{rules: [hello]}]}]")
+    ///     .unwrap();
+    /// let base = Arc::new(base);
+    ///
+    /// let mut french = Scribe::overlay(base.clone());
+    /// french
+    ///     .load_cognates_str("[{name: greeting, groups: [{rules: [bonjour]}]}]")
+    ///     .unwrap();
+    /// assert_eq!(french.gen("greeting").unwrap(), "bonjour");
+    /// assert_eq!(base.gen("greeting").unwrap(), "hello");
+    /// ```
+    pub fn overlay(base: Arc<Scribe>) -> Self {
+        Scribe {
+            cognates: HashMap::new(),
+            limits: base.limits,
+            articles: base.articles.clone(),
+            transforms: base.transforms.clone(),
+            selection: base.selection,
+            merge_policy: base.merge_policy,
+            base: Some(base),
+            hidden: HashSet::new(),
+            hidden_groups: HashMap::new(),
         }
-        Ok(())
+    }
+
+    /// Get the Scribe this one overlays, if any.
+    pub fn base(&self) -> Option<&Arc<Scribe>> {
+        self.base.as_ref()
+    }
+
+    /// Hide a Cognate of the Scribe this one overlays, so that it can no
+    /// longer be expanded through this Scribe unless this Scribe has a
+    /// Cognate of its own with the same name.
+    pub fn hide_cognate(&mut self, name: &str) {
+        self.hidden.insert(name.to_string());
+    }
+
+    /// Hide a Group of a Cognate of the Scribe this one overlays, given its
+    /// index among the Groups of the Cognate as seen through that Scribe.
+    ///
+    /// This has no effect while this Scribe has a Cognate of the same name
+    /// which does not extend the base's, as all of the base's Groups are then
+    /// hidden already.
+    pub fn hide_group(&mut self, name: &str, index: usize) {
+        self.hidden_groups
+            .entry(name.to_string())
+            .or_default()
+            .push(index);
+    }
+
+    /// Get a Cognate as seen through this Scribe and the Scribes it overlays.
+    pub(crate) fn view(&self, name: &str) -> Option<View<'_>> {
+        let own = self.cognates.get(name);
+        let below = match (&self.base, own) {
+            (Some(base), None) | (Some(base), Some(Cognate { extend: true, .. }))
+                if !self.hidden.contains(name) =>
+            {
+                base.view(name)
+            }
+            _ => None,
+        };
+        let mut view = match (below, own) {
+            (Some(mut below), _) => {
+                if let Some(indices) = self.hidden_groups.get(name) {
+                    below.hide(indices);
+                }
+                below
+            }
+            (None, Some(cognate)) => View {
+                name: &cognate.name,
+                selection: None,
+                groups: vec![],
+            },
+            (None, None) => return None,
+        };
+        if let Some(cognate) = own {
+            view.groups.extend(cognate.iter_groups());
+            view.selection = cognate.selection.or(view.selection);
+        }
+        Some(view)
+    }
+
+    /// Get the names of every Cognate visible through this Scribe and the
+    /// Scribes it overlays.
+    pub(crate) fn names(&self) -> BTreeSet<&str> {
+        let mut names = match &self.base {
+            Some(base) => base
+                .names()
+                .into_iter()
+                .filter(|name| !self.hidden.contains(*name))
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        names.extend(self.cognates.keys().map(String::as_str));
+        names
+    }
+
+    /// Get every Cognate visible through this Scribe and the Scribes it
+    /// overlays, in order of name.
+    pub(crate) fn views(&self) -> Vec<View<'_>> {
+        self.names()
+            .into_iter()
+            .filter_map(|name| self.view(name))
+            .collect()
+    }
+
+    /// Load a list of Cognates from a file, inserting them into this Scribe.
+    /// The file's format is chosen by its extension, as described for
+    /// [`Format::from_path`](format/enum.Format.html#method.from_path).
+    pub fn load_cognates(&mut self, path: &str) -> Result<(), AnnalsError> {
+        let data = std::fs::read_to_string(path)?;
+        self.load_cognates_str_as(&data, Format::from_path(path))
     }
 
     /// Load a list of Cognates from a YAML string, inserting them into this Scribe.
     pub fn load_cognates_str(&mut self, data: &str) -> Result<(), AnnalsError> {
-        let cogs: Vec<Cognate> = serde_yaml::from_str(data)?;
-        for cog in cogs {
-            self.insert_cognate(cog);
+        self.load_cognates_str_as(data, Format::Yaml)
+    }
+
+    /// Load a list of Cognates from a string in the given format, inserting
+    /// them into this Scribe.
+    pub fn load_cognates_str_as(&mut self, data: &str, format: Format) -> Result<(), AnnalsError> {
+        let cogs: Vec<Cognate> = format.deserialize_list(data)?;
+        self.insert_checked(cogs)
+    }
+
+    /// Load a grammar from a file and every file it includes, inserting
+    /// its Cognates into this Scribe. A file's list may contain `include:`
+    /// entries naming other files, relative to it, or glob patterns; each file
+    /// is loaded once, however often it is included. Errors name the file in
+    /// which they occurred, and no Cognates are inserted if any file fails.
+    pub fn load_project(&mut self, path: &str) -> Result<(), AnnalsError> {
+        let mut loader = Loader::default();
+        loader.load(Path::new(path))?;
+        for (file, cog) in loader.cognates.iter() {
+            self.check_commands(cog).map_err(|err| in_file(file, err))?;
+        }
+        if let Some(index) = self.find_duplicate(loader.cognates.iter().map(|(_, cog)| cog)) {
+            let (file, cog) = &loader.cognates[index];
+            return Err(in_file(file, AnnalsError::duplicate(cog)));
+        }
+        for (_, cog) in loader.cognates {
+            self.merge_cognate(cog);
         }
         Ok(())
     }
 
+    /// Load a tracery grammar from a JSON file, inserting a Cognate for each
+    /// symbol into this Scribe. Returns a warning for each rule or modifier
+    /// which could not be translated and was skipped.
+    #[cfg(feature = "json")]
+    pub fn load_tracery_json(&mut self, path: &str) -> Result<Vec<tracery::Warning>, AnnalsError> {
+        let data = std::fs::read_to_string(path)?;
+        self.load_tracery_json_str(&data)
+    }
+
+    /// Load a tracery grammar from a JSON string, inserting a Cognate for each
+    /// symbol into this Scribe. Returns a warning for each rule or modifier
+    /// which could not be translated and was skipped.
+    #[cfg(feature = "json")]
+    pub fn load_tracery_json_str(
+        &mut self,
+        data: &str,
+    ) -> Result<Vec<tracery::Warning>, AnnalsError> {
+        let (cogs, warnings) = tracery::import(data)?;
+        self.merge_cognates(cogs)?;
+        Ok(warnings)
+    }
+
+    /// Convert this Scribe's Cognates into a tracery grammar in JSON form.
+    /// Returns the JSON along with a warning for each feature, such as group
+    /// tags, ranges and `@` bindings, which has no tracery equivalent. An
+    /// overlay converts every Cognate visible through it.
+    #[cfg(feature = "json")]
+    pub fn to_tracery_json(&self) -> Result<(String, Vec<tracery::Warning>), AnnalsError> {
+        let (symbols, warnings) = tracery::export(self);
+        let json = serde_json::to_string_pretty(&symbols)?;
+        Ok((json, warnings))
+    }
+
+    /// Save this Scribe's Cognates to a JSON file as a tracery grammar.
+    /// Returns a warning for each feature, such as group tags, ranges and `@`
+    /// bindings, which has no tracery equivalent. An overlay saves every
+    /// Cognate visible through it.
+    #[cfg(feature = "json")]
+    pub fn save_tracery_json(&self, path: &str) -> Result<Vec<tracery::Warning>, AnnalsError> {
+        let (symbols, warnings) = tracery::export(self);
+        let f = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(f, &symbols)?;
+        Ok(warnings)
+    }
+
+    /// Move the Cognates of another Scribe into this one, according to this
+    /// Scribe's merge policy. Fails without merging any Cognates if a Rule
+    /// uses a command not registered with this Scribe, or if the policy
+    /// forbids a name clash.
+    pub fn merge(&mut self, other: Scribe) -> Result<(), AnnalsError> {
+        self.insert_checked(other.cognates.into_values().collect())
+    }
+
     /// Create and return a new Cognate.
     pub fn cognate(&mut self, name: &str) -> &mut Cognate {
         self.cognates
@@ -63,130 +300,567 @@ impl Scribe {
             .or_insert_with(|| Cognate::new(name))
     }
 
-    /// Insert a Cognate.
+    /// Insert a Cognate, replacing any with the same name regardless of the
+    /// merge policy.
     pub fn insert_cognate(&mut self, cognate: Cognate) {
         self.cognates.insert(cognate.name.to_string(), cognate);
     }
 
-    /// Iterate over Cognates in this Scribe.
-    pub fn iter(&self) -> std::collections::hash_map::Values<String, Cognate> {
+    /// Insert loaded Cognates, failing without inserting any if a Rule uses
+    /// an unregistered command.
+    fn insert_checked(&mut self, cogs: Vec<Cognate>) -> Result<(), AnnalsError> {
+        cogs.iter().try_for_each(|cog| self.check_commands(cog))?;
+        self.merge_cognates(cogs)
+    }
+
+    /// Insert loaded Cognates according to the merge policy, failing without
+    /// inserting any if the policy forbids a name clash.
+    fn merge_cognates(&mut self, cogs: Vec<Cognate>) -> Result<(), AnnalsError> {
+        if let Some(index) = self.find_duplicate(&cogs) {
+            return Err(AnnalsError::duplicate(&cogs[index]));
+        }
+        for cog in cogs {
+            self.merge_cognate(cog);
+        }
+        Ok(())
+    }
+
+    /// Insert a loaded Cognate according to the merge policy, which must
+    /// already have been checked with `find_duplicate`. A Cognate marked
+    /// `extend` is appended to an existing one whatever the policy.
+    fn merge_cognate(&mut self, mut cognate: Cognate) {
+        let append = cognate.extend || self.merge_policy == MergePolicy::Append;
+        // Layer a Cognate over one in the base, rather than replacing it.
+        let in_base = matches!(&self.base, Some(base) if base.view(&cognate.name).is_some());
+        if append && in_base && !self.cognates.contains_key(&cognate.name) {
+            cognate.extend = true;
+        }
+        match self.cognates.get_mut(&cognate.name) {
+            Some(existing) if append => existing.append(cognate),
+            _ => self.insert_cognate(cognate),
+        }
+    }
+
+    /// Under `MergePolicy::Error`, find the index of the first Cognate whose
+    /// name is already taken, or repeats that of an earlier one, and which is
+    /// not marked `extend`.
+    fn find_duplicate<'a, I>(&self, cogs: I) -> Option<usize>
+    where
+        I: IntoIterator<Item = &'a Cognate>,
+    {
+        if self.merge_policy != MergePolicy::Error {
+            return None;
+        }
+        let mut seen = HashSet::new();
+        cogs.into_iter().position(|cog| {
+            let taken = self.view(&cog.name).is_some() || !seen.insert(cog.name.as_str());
+            taken && !cog.extend
+        })
+    }
+
+    /// Find the Cognate to which `name` refers in a Rule of a Cognate in
+    /// `namespace`. Names are looked up within that namespace first, so that
+    /// `<class>` in `ships::ship` means `ships::class` if there is one, and
+    /// otherwise the top-level `class`.
+    pub(crate) fn resolve(&self, name: &str, namespace: &str) -> Option<View<'_>> {
+        if !namespace.is_empty() {
+            if let Some(view) = self.view(&format!("{}::{}", namespace, name)) {
+                return Some(view);
+            }
+        }
+        self.view(name)
+    }
+
+    /// Check that every command used by a Cognate's Rules is registered.
+    fn check_commands(&self, cognate: &Cognate) -> Result<(), AnnalsError> {
+        for rule in cognate.iter_groups().flat_map(|grp| grp.rules.iter()) {
+            self.check_rule_commands(rule)?;
+        }
+        Ok(())
+    }
+
+    /// Check that every command used by a Rule is registered.
+    fn check_rule_commands(&self, rule: &Rule) -> Result<(), AnnalsError> {
+        match rule
+            .tokens()
+            .iter()
+            .find_map(|token| unknown_command(token, &self.transforms))
+        {
+            Some(err) => Err(AnnalsError::from_invalid_rule(
+                rule.literal().to_string(),
+                err,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Iterate over Cognates in this Scribe, not including those of any Scribe
+    /// it overlays. The save methods, by contrast, write every Cognate visible
+    /// through this Scribe.
+    pub fn iter(&self) -> std::collections::hash_map::Values<'_, String, Cognate> {
         self.cognates.values()
     }
 
+    /// Get the limits applied when generating text.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Set the limits applied when generating text.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Get the way Rules are chosen from Cognates which do not set their own.
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    /// Set the way Rules are chosen from Cognates which do not set their own.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
+    }
+
+    /// Get what happens when a loaded Cognate has the same name as another.
+    pub fn merge_policy(&self) -> MergePolicy {
+        self.merge_policy
+    }
+
+    /// Set what happens when a loaded Cognate has the same name as another.
+    pub fn set_merge_policy(&mut self, policy: MergePolicy) {
+        self.merge_policy = policy;
+    }
+
+    /// Add an exception to the rules used by the `(an ...)` command: phrases
+    /// whose first word begins with `prefix`, ignoring case, take `article`.
+    /// Where several exceptions match, the longest prefix wins.
+    ///
+    /// ```
+    /// use annals::Scribe;
+    ///
+    /// let mut scribe = Scribe::default();
+    /// scribe
+    ///     .load_cognates_str("[{name: job, groups: [{rules: [herbalist]}]}]")
+    ///     .unwrap();
+    /// assert_eq!(scribe.expand("<(an job)>").unwrap(), "a herbalist");
+    /// scribe.add_article_exception("herb", "an");
+    /// assert_eq!(scribe.expand("<(an job)>").unwrap(), "an herbalist");
+    /// ```
+    pub fn add_article_exception(&mut self, prefix: &str, article: &str) {
+        self.articles
+            .insert(prefix.to_lowercase(), article.to_string());
+    }
+
+    /// Register a Transform under `name`, for use as a command in rules such
+    /// as `<(name ...)>`. Built-in commands such as `cap` cannot be replaced.
+    ///
+    /// Commands are resolved when Cognates are loaded, so Transforms must be
+    /// registered before loading any rules which use them.
+    pub fn register_transform<T: Transform + 'static>(&mut self, name: &str, transform: T) {
+        self.transforms.insert(name, Arc::new(transform));
+    }
+
+    /// Check the grammar for references to unknown Cognates, unregistered
+    /// commands, Cognates with no Rules and Cognates whose expansion can never
+    /// end, returning every problem found. If `root` is given, Cognates which
+    /// cannot be reached from it are reported too.
+    pub fn validate(&self, root: Option<&str>) -> Vec<Diagnostic> {
+        validate::check(self, root)
+    }
+
     /// Generate text from a named Cognate.
     pub fn gen(&self, cognate: &str) -> Result<String, AnnalsError> {
-        let mut context = Context::default();
-        let sel = self.select_rule(cognate, &mut context)?;
-        self.expand_tokens(sel.tokens(), &mut context)
+        self.gen_with_rng(cognate, Context::default(), &mut thread_rng())
     }
 
     /// Generate text from a named Cognate using the passed Context.
-    pub fn gen_with(&self, cognate: &str, mut context: Context) -> Result<String, AnnalsError> {
-        let sel = self.select_rule(cognate, &mut context)?;
-        self.expand_tokens(sel.tokens(), &mut context)
+    pub fn gen_with(&self, cognate: &str, context: Context) -> Result<String, AnnalsError> {
+        self.gen_with_rng(cognate, context, &mut thread_rng())
+    }
+
+    /// Generate text from a named Cognate using the passed Context, drawing
+    /// all random choices from `rng`.
+    pub fn gen_with_rng<R: Rng>(
+        &self,
+        cognate: &str,
+        mut context: Context,
+        rng: &mut R,
+    ) -> Result<String, AnnalsError> {
+        self.expand_cognate(cognate, &mut context, rng)
+    }
+
+    /// Generate text from a named Cognate using the passed Context and a
+    /// seeded RNG. The same seed, grammar and Context always produce the same
+    /// output.
+    pub fn gen_seeded(
+        &self,
+        cognate: &str,
+        context: Context,
+        seed: u64,
+    ) -> Result<String, AnnalsError> {
+        self.gen_with_rng(cognate, context, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Generate text from a named Cognate using the passed Context, returning
+    /// the text along with the tree of expansions which produced it.
+    pub fn gen_traced(
+        &self,
+        cognate: &str,
+        context: Context,
+    ) -> Result<(String, Derivation), AnnalsError> {
+        self.gen_traced_with_rng(cognate, context, &mut thread_rng())
+    }
+
+    /// Generate text and its derivation tree from a named Cognate using the
+    /// passed Context, drawing all random choices from `rng`.
+    pub fn gen_traced_with_rng<R: Rng>(
+        &self,
+        cognate: &str,
+        mut context: Context,
+        rng: &mut R,
+    ) -> Result<(String, Derivation), AnnalsError> {
+        context.start_trace();
+        let ret = self.expand_cognate(cognate, &mut context, rng)?;
+        let tree = context
+            .tracer()
+            .and_then(|tracer| tracer.finish())
+            .ok_or(AnnalsError::UnknownError)?;
+        Ok((ret, tree))
     }
 
     /// Generate text from the passed template string.
     pub fn expand(&self, rule: &str) -> Result<String, AnnalsError> {
-        let new_rule = Rule::new(rule)?;
-        let mut context = Context::default();
-        self.expand_tokens(new_rule.tokens(), &mut context)
+        self.expand_with_rng(rule, Context::default(), &mut thread_rng())
     }
 
     /// Generate text from the passed template string and Context.
-    pub fn expand_with(&self, rule: &str, mut context: Context) -> Result<String, AnnalsError> {
+    pub fn expand_with(&self, rule: &str, context: Context) -> Result<String, AnnalsError> {
+        self.expand_with_rng(rule, context, &mut thread_rng())
+    }
+
+    /// Generate text from the passed template string and Context, drawing
+    /// all random choices from `rng`.
+    pub fn expand_with_rng<R: Rng>(
+        &self,
+        rule: &str,
+        mut context: Context,
+        rng: &mut R,
+    ) -> Result<String, AnnalsError> {
         let new_rule = Rule::new(rule)?;
-        self.expand_tokens(new_rule.tokens(), &mut context)
+        self.check_rule_commands(&new_rule)?;
+        self.expand_tokens(new_rule.tokens(), &mut context, rng)
     }
-    /// Save this Scribe to a YAML file.
+
+    /// Generate text from the passed template string and Context using a
+    /// seeded RNG.
+    pub fn expand_seeded(
+        &self,
+        rule: &str,
+        context: Context,
+        seed: u64,
+    ) -> Result<String, AnnalsError> {
+        self.expand_with_rng(rule, context, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Save this Scribe to a file, in the format given by its extension.
+    ///
+    /// An overlay is saved as a single Scribe holding every Cognate visible
+    /// through it, with the Groups of its base which it does not hide.
     pub fn save(&self, path: &str) -> Result<(), AnnalsError> {
-        let f = File::create(path)?;
-        serde_yaml::to_writer(f, &self).map_err(Into::into)
+        let flattened = Flattened {
+            cognates: self
+                .views()
+                .into_iter()
+                .map(|view| (view.name, view))
+                .collect(),
+        };
+        let data = Format::from_path(path).serialize(&flattened)?;
+        std::fs::write(path, data).map_err(Into::into)
     }
 
-    /// Save this Scribe's Cognates to a YAML file.
+    /// Save this Scribe's Cognates to a file, in the format given by its
+    /// extension. As with [`save`](#method.save), an overlay writes every
+    /// Cognate visible through it.
     pub fn save_cognates(&self, path: &str) -> Result<(), AnnalsError> {
-        let f = File::create(path)?;
-        let cognates: Vec<&Cognate> = self.cognates.values().collect();
-        serde_yaml::to_writer(f, &cognates).map_err(Into::into)
-    }
-
-    /// Select a template from a named Cognate using the passed Context.
-    fn select_rule(&self, name: &str, context: &mut Context) -> Result<&Rule, AnnalsError> {
-        match self.cognates.get(name) {
-            Some(cognate) => {
-                if cognate.is_empty() {
-                    return Err(AnnalsError::EmptyCognate {
-                        name: name.to_string(),
-                    });
-                }
-                let groups = cognate
-                    .iter_groups()
-                    .filter(|grp| context.accept_strict(grp))
-                    .collect::<Vec<_>>();
-                if groups.is_empty() {
-                    return Err(AnnalsError::NoSuitableGroups {
-                        name: name.to_string(),
-                        context: format!("{:?}", context.tags),
-                    });
-                }
-                let mut templates = GroupListIter::new(groups);
-                if templates.size == 0 {
-                    return Err(AnnalsError::EmptyCognate {
-                        name: name.to_string(),
-                    });
-                }
-                let index = thread_rng().gen_range(0, templates.size);
-                match templates.nth(index) {
-                    Some(template) => {
-                        context.merge_from_group(template.1);
-                        Ok(template.0)
-                    }
-                    None => Err(AnnalsError::EmptyCognate {
-                        name: name.to_string(),
-                    }),
+        let data = Format::from_path(path).serialize_list(self.views())?;
+        std::fs::write(path, data).map_err(Into::into)
+    }
+
+    /// Select a template from a Cognate using the passed Context.
+    fn select_rule<'a>(
+        &self,
+        cognate: &View<'a>,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<(&'a Rule, &'a Group), AnnalsError> {
+        if cognate.groups.is_empty() {
+            return Err(AnnalsError::EmptyCognate {
+                name: cognate.name.to_string(),
+            });
+        }
+        let mut groups = cognate
+            .groups
+            .iter()
+            .copied()
+            .filter(|grp| context.accept_strict(grp))
+            .collect::<Vec<_>>();
+        if groups.is_empty() {
+            return Err(AnnalsError::NoSuitableGroups {
+                name: cognate.name.to_string(),
+                context: format!("{:?}", context.tags),
+            });
+        }
+        let selection = cognate.selection.unwrap_or(self.selection);
+        if selection == Selection::Salient {
+            let best = groups.iter().map(|grp| context.salience(grp)).max();
+            groups.retain(|grp| Some(context.salience(grp)) == best);
+        }
+        let templates = GroupListIter::new(groups).collect::<Vec<_>>();
+        let weights = templates
+            .iter()
+            .map(|(rule, group)| match selection {
+                Selection::Weighted => {
+                    rule.weight() * group.weight * (context.salience(group) + 1) as f64
                 }
-            }
-            None => Err(AnnalsError::UnknownCognate {
-                name: name.to_string(),
+                _ => rule.weight() * group.weight,
+            })
+            .collect::<Vec<_>>();
+        match choose_weighted(&weights, rng) {
+            Some(index) => Ok(templates[index]),
+            None => Err(AnnalsError::EmptyCognate {
+                name: cognate.name.to_string(),
             }),
         }
     }
 
+    /// Select a template from a named Cognate and expand it into a String.
+    fn expand_cognate(
+        &self,
+        name: &str,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<String, AnnalsError> {
+        self.expand_cognate_group(name, context, rng)
+            .map(|(ret, _)| ret)
+    }
+
+    /// Select a template from a named Cognate and expand it into a String,
+    /// also returning whether the template's Group binds its result.
+    fn expand_cognate_group(
+        &self,
+        name: &str,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<(String, bool), AnnalsError> {
+        let (depth, expansions) = context.enter();
+        if let Some(limit) = self.limits.max_depth {
+            if depth > limit {
+                return Err(AnnalsError::DepthLimitExceeded {
+                    name: name.to_string(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.limits.max_expansions {
+            if expansions > limit {
+                return Err(AnnalsError::ExpansionLimitExceeded { limit });
+            }
+        }
+        let cognate = match self.resolve(name, context.namespace()) {
+            Some(cognate) => cognate,
+            None => {
+                return Err(AnnalsError::UnknownCognate {
+                    name: name.to_string(),
+                })
+            }
+        };
+        let (rule, group) = self.select_rule(&cognate, context, rng)?;
+        let saved = match group.scope {
+            TagScope::Propagate => None,
+            _ => Some(context.tags.clone()),
+        };
+        context.merge_from_group(group);
+        if let Some(tracer) = context.tracer() {
+            tracer.open(cognate.name, group, rule);
+        }
+        let outer = context.set_namespace(cognate.namespace().to_string());
+        let ret = self.expand_tokens(rule.tokens(), context, rng)?;
+        context.set_namespace(outer);
+        if let Some(plural) = rule.plural() {
+            context.add_plural(&ret, plural);
+        }
+        if let Some(tracer) = context.tracer() {
+            tracer.close(ret.len());
+        }
+        match (saved, &group.scope) {
+            (Some(saved), TagScope::Export(keys)) => context.restore_tags(saved, keys),
+            (Some(saved), _) => context.restore_tags(saved, &[]),
+            (None, _) => (),
+        }
+        context.leave();
+        Ok((ret, group.binds()))
+    }
+
     /// Expand an iterator over a sequence of Tokens into a String.
     #[inline]
     fn expand_tokens(
         &self,
         tokens: &[Token],
         context: &mut Context,
+        rng: &mut dyn RngCore,
     ) -> Result<String, AnnalsError> {
-        let ret = tokens
-            .iter()
-            .map(|tok| self.handle_token(tok, context))
-            .collect::<Result<Vec<_>, AnnalsError>>()?
-            .join("");
+        let start = context.tracer().map(|tracer| tracer.cursor);
+        let mut ret = String::new();
+        // Set after an optional segment, choice, repetition or tag token
+        // expands to nothing, so that the whitespace around it can be collapsed.
+        let mut squeeze = false;
+        for tok in tokens {
+            let mut text = self.handle_token(tok, context, rng)?;
+            if squeeze && matches!(tok, Token::Literal(_)) {
+                text = squeeze_whitespace(&mut ret, &text);
+                squeeze = text.is_empty();
+            } else {
+                squeeze = text.is_empty()
+                    && matches!(
+                        tok,
+                        Token::Optional(..)
+                            | Token::Choice(_)
+                            | Token::Repeat { .. }
+                            | Token::SetTag(..)
+                            | Token::ClearTag(_)
+                    );
+            }
+            ret.push_str(&text);
+            if let Some(limit) = self.limits.max_output {
+                if ret.len() > limit {
+                    return Err(AnnalsError::OutputLimitExceeded { limit });
+                }
+            }
+            if let (Some(start), Some(tracer)) = (start, context.tracer()) {
+                tracer.cursor = start + ret.len();
+            }
+        }
+        if squeeze {
+            ret.truncate(ret.trim_end().len());
+        }
         Ok(ret)
     }
 
-    fn expand_name(&self, name: &str, context: &mut Context) -> Result<String, AnnalsError> {
-        context.descend();
+    fn expand_name(
+        &self,
+        name: &str,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<String, AnnalsError> {
         if let Some(bind) = context.get_binding(name) {
             return Ok(bind);
         }
-        let sel = self.select_rule(name, context)?;
-        let ret = self.expand_tokens(sel.tokens(), context);
+        context.descend();
+        let ret = self.expand_cognate_group(name, context, rng);
         context.ascend();
-        ret
+        let (ret, binds) = ret?;
+        if binds {
+            context.bind(name, &ret);
+        }
+        Ok(ret)
+    }
+
+    /// Expand a named Cognate for binding to a variable, rather than output.
+    fn expand_binding(
+        &self,
+        name: &str,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<String, AnnalsError> {
+        let mark = context.tracer().map(|tracer| tracer.mark());
+        let (ret, binds) = self.expand_cognate_group(name, context, rng)?;
+        if let (Some(mark), Some(tracer)) = (mark, context.tracer()) {
+            tracer.collapse(mark);
+        }
+        if binds {
+            context.bind(name, &ret);
+        }
+        Ok(ret)
+    }
+
+    /// Expand a Token `count` times, joining the results with `sep`, or `last`
+    /// before the final one. If `unique`, repeated results are dropped, so
+    /// fewer may be produced.
+    #[allow(clippy::too_many_arguments)]
+    fn expand_repeat(
+        &self,
+        token: &Token,
+        count: usize,
+        sep: &str,
+        last: &str,
+        unique: bool,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<String, AnnalsError> {
+        let start = context.tracer().map(|tracer| tracer.cursor);
+        let mut ret = String::new();
+        let mut items: Vec<String> = vec![];
+        let mut final_item = (0, None);
+        let mut retries = 0;
+        while items.len() < count && retries < count.saturating_mul(UNIQUE_RETRIES) {
+            let expansions = context.count_expansion();
+            if let Some(limit) = self.limits.max_expansions {
+                if expansions > limit {
+                    return Err(AnnalsError::ExpansionLimitExceeded { limit });
+                }
+            }
+            let joiner = match items.len() {
+                0 => "",
+                n if n + 1 == count => last,
+                _ => sep,
+            };
+            if let (Some(start), Some(tracer)) = (start, context.tracer()) {
+                tracer.cursor = start + ret.len() + joiner.len();
+            }
+            let mark = context.tracer().map(|tracer| tracer.mark());
+            let text = self.handle_token(token, context, rng)?;
+            if unique && items.contains(&text) {
+                if let (Some(mark), Some(tracer)) = (mark, context.tracer()) {
+                    tracer.discard(mark);
+                }
+                retries += 1;
+                continue;
+            }
+            final_item = (ret.len(), mark);
+            ret.push_str(joiner);
+            ret.push_str(&text);
+            items.push(text);
+            if let Some(limit) = self.limits.max_output {
+                if ret.len() > limit {
+                    return Err(AnnalsError::OutputLimitExceeded { limit });
+                }
+            }
+        }
+        // Ran out of unique results, so the final item was joined with `sep`.
+        if items.len() > 1 && items.len() < count {
+            let (offset, mark) = final_item;
+            ret.truncate(offset);
+            ret.push_str(last);
+            if let (Some(start), Some(mark), Some(tracer)) = (start, mark, context.tracer()) {
+                tracer.relocate(mark, start + ret.len());
+            }
+            ret.push_str(&items[items.len() - 1]);
+        }
+        Ok(ret)
     }
 
     /// Recursively expand a token to a String.
-    fn handle_token(&self, token: &Token, context: &mut Context) -> Result<String, AnnalsError> {
+    fn handle_token(
+        &self,
+        token: &Token,
+        context: &mut Context,
+        rng: &mut dyn RngCore,
+    ) -> Result<String, AnnalsError> {
         match token {
             Token::Literal(text) => Ok(text.clone()),
-            Token::NonTerminal(name) => self.expand_name(name, context),
-            Token::StickyNonTerminal(name) => self.expand_name(name, context).and_then(|ret| {
-                context.bind(name, &ret);
-                Ok(ret)
+            Token::NonTerminal(name) => self.expand_name(name, context, rng),
+            Token::StickyNonTerminal(name) => self.expand_name(name, context, rng).inspect(|ret| {
+                context.bind(name, ret);
             }),
             Token::Binding(name) => {
                 if let Some(bind) = context.get_binding(name) {
@@ -194,62 +868,185 @@ impl Scribe {
                 }
                 Err(AnnalsError::UnboundVariable { name: name.clone() })
             }
-            Token::Expression(cmd, token) => {
-                match cmd {
-                    Command::Capitalize => self.handle_token(token, context).and_then(|ret| {
-                        let mut chs = ret.chars();
+            Token::Expression(cmd, body) => {
+                let mark = context.tracer().map(|tracer| tracer.mark());
+                let inner = self.expand_tokens(body, context, rng)?;
+                let ret = match cmd {
+                    Command::Capitalize => {
+                        let mut chs = inner.chars();
                         match chs.next() {
-                            Some(t) => Ok(t.to_uppercase().chain(chs).collect()),
-                            None => Ok("".to_string()),
+                            Some(t) => t.to_uppercase().chain(chs).collect(),
+                            None => "".to_string(),
                         }
-                    }),
-                    Command::Lowercase => self
-                        .handle_token(token, context)
-                        .and_then(|ret| Ok(ret.to_lowercase())),
-                    Command::Titlecase => self
-                        .handle_token(token, context)
-                        .and_then(|ret| Ok(titlecase(&ret))),
+                    }
+                    Command::Lowercase => inner.to_lowercase(),
+                    Command::Titlecase => titlecase(&inner),
                     Command::IndefiniteArticle => {
-                        self.handle_token(token, context).and_then(|ret| {
-                            match &ret.chars().next() {
-                                // TODO: Stopgap; replace.
-                                Some(ch) => match ch {
-                                    'a' | 'e' | 'i' | 'o' | 'u' | 'A' | 'E' | 'I' | 'O' | 'U' => {
-                                        Ok(format!("an {}", ret))
-                                    }
-                                    _ => Ok(format!("a {}", ret)),
-                                },
-                                None => Ok("".to_string()),
-                            }
-                        })
+                        if inner.is_empty() {
+                            inner.clone()
+                        } else {
+                            let article = inflect::indefinite_article(&inner, &self.articles);
+                            format!("{} {}", article, inner)
+                        }
                     }
+                    Command::Plural => context
+                        .plural_of(&inner)
+                        .unwrap_or_else(|| inflect::pluralize(&inner)),
+                    Command::Truncate(len) => inner.chars().take(*len).collect(),
+                    Command::Custom(name, args, ..) => match self.transforms.get(name) {
+                        Some(transform) => transform.apply_with_args(args, &inner),
+                        None => return Err(AnnalsError::UnknownCommand { name: name.clone() }),
+                    },
+                };
+                if let (Some(mark), Some(tracer)) = (mark, context.tracer()) {
+                    let growth = match cmd {
+                        Command::IndefiniteArticle => Growth::Prefix,
+                        Command::Custom(..) => Growth::Unknown,
+                        _ => Growth::Suffix,
+                    };
+                    tracer.realign(mark, inner.len(), ret.len(), growth);
                 }
+                Ok(ret)
             }
-            Token::Range(lower, upper) => Ok(thread_rng().gen_range(*lower, *upper).to_string()),
-            Token::VariableAssignment(name, bind) => {
-                if context.get_binding(name).is_some() {
-                    return Ok("".to_string());
+            Token::Range(lower, upper) => Ok(rng.gen_range(*lower, *upper).to_string()),
+            Token::Weight(_) | Token::PluralForm(_) => Ok("".to_string()),
+            Token::Choice(choices) => {
+                let weights = choices
+                    .iter()
+                    .map(|(_, weight)| *weight)
+                    .collect::<Vec<_>>();
+                match choose_weighted(&weights, rng) {
+                    Some(index) => self.expand_tokens(&choices[index].0, context, rng),
+                    None => Ok("".to_string()),
+                }
+            }
+            Token::Repeat {
+                token,
+                min,
+                max,
+                sep,
+                last,
+                unique,
+            } => {
+                let count = rng.gen_range(*min, *max + 1);
+                let last = last.as_ref().unwrap_or(sep);
+                self.expand_repeat(token, count, sep, last, *unique, context, rng)
+            }
+            Token::SetTag(key, value) => {
+                context.set(key, value);
+                Ok("".to_string())
+            }
+            Token::ClearTag(key) => {
+                context.tags.remove(key);
+                Ok("".to_string())
+            }
+            Token::Optional(probability, tokens) => {
+                if rng.gen_bool(*probability) {
+                    self.expand_tokens(tokens, context, rng)
+                } else {
+                    Ok("".to_string())
                 }
-                let srule = self.select_rule(bind, context)?;
-                let bind = self.expand_tokens(srule.tokens(), context)?;
-                context.bind(name, &bind);
-                let ret = self.expand_name(name, context);
-                // TODO: exiting the 'scope' of a property, we drop the
-                // property's bindings, but bindings, but it may be _optionally_
-                // desirable to do so for tags as well.
-                context.unbind(name);
-                ret
             }
+            Token::VariableAssignment(bindings, body) => match body {
+                Some(body) => {
+                    let mut shadowed = vec![];
+                    for (var, cognate) in bindings {
+                        let value = self.expand_binding(cognate, context, rng)?;
+                        shadowed.push((var, context.shadow(var, &value)));
+                    }
+                    let ret = self.handle_token(body, context, rng);
+                    // Tags set while expanding the bindings are scoped by
+                    // their groups, not by the assignment.
+                    for (var, previous) in shadowed.into_iter().rev() {
+                        context.restore(var, previous);
+                    }
+                    ret
+                }
+                None => {
+                    for (var, cognate) in bindings {
+                        let value = self.expand_binding(cognate, context, rng)?;
+                        context.bind(var, &value);
+                    }
+                    Ok("".to_string())
+                }
+            },
         }
     }
 }
 
+/// Drop the whitespace at the start of `text` if there is already whitespace
+/// at the end of `ret`, or nothing before it. If `text` then begins with
+/// punctuation, the whitespace at the end of `ret` is dropped instead.
+fn squeeze_whitespace(ret: &mut String, text: &str) -> String {
+    if !ret.is_empty() && !ret.ends_with(char::is_whitespace) {
+        return text.to_string();
+    }
+    let text = text.trim_start();
+    if text.starts_with(|ch| ",.;:!?)".contains(ch)) {
+        ret.truncate(ret.trim_end().len());
+    }
+    text.to_string()
+}
+
+/// Find the first command in a Token which is neither built in nor registered.
+fn unknown_command(token: &Token, transforms: &Transforms) -> Option<ParseError> {
+    match token {
+        Token::Expression(Command::Custom(name, _, beg, end), _) if !transforms.contains(name) => {
+            Some(ParseError::UnknownCommand(*beg, *end))
+        }
+        Token::Expression(_, body) => body
+            .iter()
+            .find_map(|token| unknown_command(token, transforms)),
+        Token::VariableAssignment(_, Some(body)) => unknown_command(body, transforms),
+        Token::Choice(choices) => choices
+            .iter()
+            .flat_map(|(tokens, _)| tokens)
+            .find_map(|token| unknown_command(token, transforms)),
+        Token::Optional(_, tokens) => tokens
+            .iter()
+            .find_map(|token| unknown_command(token, transforms)),
+        Token::Repeat { token, .. } => unknown_command(token, transforms),
+        _ => None,
+    }
+}
+
+/// Number of attempts per item made by a `unique` repetition to find a result
+/// which has not already been produced.
+const UNIQUE_RETRIES: usize = 10;
+
+/// Pick an index at random, with each index's likelihood proportional to its
+/// weight. Returns `None` if no index has a positive weight.
+fn choose_weighted(weights: &[f64], rng: &mut dyn RngCore) -> Option<usize> {
+    let total: f64 = weights.iter().filter(|w| **w > 0.0).sum();
+    if total <= 0.0 || !total.is_finite() {
+        return None;
+    }
+    let mut point = rng.gen_range(0.0, total);
+    let mut last = None;
+    for (index, weight) in weights.iter().enumerate() {
+        if *weight <= 0.0 {
+            continue;
+        }
+        if point < *weight {
+            return Some(index);
+        }
+        point -= weight;
+        last = Some(index);
+    }
+    // Floating-point error may leave a sliver past the final weight.
+    last
+}
+
 impl FromStr for Scribe {
     type Err = AnnalsError;
 
     /// Create a new Scribe from a YAML string.
     fn from_str(data: &str) -> Result<Self, AnnalsError> {
-        serde_yaml::from_str(data).map_err(Into::into)
+        let scribe: Scribe = serde_yaml::from_str(data)?;
+        scribe
+            .iter()
+            .try_for_each(|cog| scribe.check_commands(cog))?;
+        Ok(scribe)
     }
 }
 
@@ -263,10 +1060,755 @@ mod test {
         let mut ctx = Context::default();
         let tok = Token::Expression(
             Command::Titlecase,
-            Box::new(Token::Literal("the duke of york".to_owned())),
+            vec![Token::Literal("the duke of york".to_owned())],
         );
-        let res = scr.handle_token(&tok, &mut ctx);
+        let res = scr.handle_token(&tok, &mut ctx, &mut thread_rng());
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), "The Duke of York".to_owned());
     }
+
+    #[test]
+    fn test_weighted_selection() {
+        let mut scr = Scribe::default();
+        scr.cognate("animal")
+            .group_from_rules(&["<^0>elephant", "<^3>mouse"])
+            .unwrap();
+        {
+            let grp = scr.cognate("animal").add_group().unwrap();
+            grp.add_rule("whale").unwrap();
+            grp.set_weight(0.0);
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let res = scr
+                .gen_with_rng("animal", Context::default(), &mut rng)
+                .unwrap();
+            assert_eq!(res, "mouse");
+        }
+
+        let mut scr = Scribe::default();
+        scr.cognate("none").group_from_rules(&["<^0>void"]).unwrap();
+        assert!(scr.gen("none").is_err());
+    }
+
+    #[test]
+    fn test_traced_generation() {
+        let mut scr = Scribe::default();
+        {
+            let grp = scr.cognate("animal").add_group().unwrap();
+            grp.add_rules(&["elephant", "owl"]).unwrap();
+            grp.set_tag("size", "any");
+        }
+        scr.cognate("root")
+            .group_from_rules(&["Is that <(an animal)>? Yes, <(cap animal)>."])
+            .unwrap();
+        let (text, tree) = scr.gen_traced("root", Context::default()).unwrap();
+        assert_eq!(tree.cognate, "root");
+        assert_eq!(tree.rule, "Is that <(an animal)>? Yes, <(cap animal)>.");
+        assert_eq!(tree.text(&text), text);
+        assert_eq!(tree.children.len(), 2);
+        for child in &tree.children {
+            assert_eq!(child.cognate, "animal");
+            assert_eq!(child.tags["size"], "any");
+            assert_eq!(child.text(&text).to_lowercase(), child.rule);
+        }
+        assert_eq!(&text[..tree.children[0].span.start], "Is that an ");
+    }
+
+    #[test]
+    fn test_traced_commands() {
+        let mut scr = Scribe::default();
+        scr.register_transform("shout", |text: &str| format!("!{}!", text));
+        scr.cognate("good")
+            .group_from_rules(&["whale oil"])
+            .unwrap();
+        scr.cognate("root")
+            .group_from_rules(&["<(plural <good>)>, <(shout <good>)>"])
+            .unwrap();
+        let (text, tree) = scr.gen_traced("root", Context::default()).unwrap();
+        assert_eq!(text, "whale oils, !whale oil!");
+        // Text appended by (plural ...) leaves the nested span in place.
+        assert_eq!(tree.children[0].span, 0..9);
+        assert_eq!(tree.children[0].text(&text), "whale oil");
+        // A custom transform may change anything, so covers the whole text.
+        assert_eq!(tree.children[1].text(&text), "!whale oil!");
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(include_str!("../texts/tracery.yml"))
+            .unwrap();
+        let mut ctx = Context::default();
+        ctx.bind("hero", "Nobody");
+        let (text, tree) = scr.gen_traced("origin", ctx.clone()).unwrap();
+        let hero = &tree.children[0];
+        assert_eq!(hero.cognate, "name");
+        assert_eq!(hero.span, 0..0);
+        assert!(text.starts_with(&format!("{} traveled", hero.rule)));
+        assert_eq!(tree.children[2].cognate, "story");
+        assert_eq!(tree.children[2].text(&text), text);
+
+        scr.cognate("tale")
+            .group_from_rules(&["<hero> rests"])
+            .unwrap();
+        scr.cognate("scoped")
+            .group_from_rules(&["<[hero:name]tale>, <hero> wakes"])
+            .unwrap();
+        let text = scr.gen_with("scoped", ctx.clone()).unwrap();
+        assert!(text.ends_with(" rests, Nobody wakes"));
+        assert!(!text.starts_with("Nobody"));
+
+        scr.cognate("unscoped")
+            .group_from_rules(&["<[hero:name]><hero> and <hero>"])
+            .unwrap();
+        let text = scr.gen_with("unscoped", ctx).unwrap();
+        let names = text.split(" and ").collect::<Vec<_>>();
+        assert_eq!(names[0], names[1]);
+        assert_ne!(names[0], "Nobody");
+    }
+
+    #[test]
+    fn test_plural() {
+        let mut scr = Scribe::default();
+        scr.cognate("animal")
+            .group_from_rules(&["octopus<~octopodes>"])
+            .unwrap();
+        scr.cognate("bird").group_from_rules(&["<animal>"]).unwrap();
+        assert_eq!(scr.expand("<(plural animal)>").unwrap(), "octopodes");
+        assert_eq!(scr.expand("<(plural bird)>").unwrap(), "octopodes");
+        assert_eq!(scr.expand("<(cap (s animal))>").unwrap(), "Octopodes");
+        assert_eq!(scr.expand("<(s (cap animal))>").unwrap(), "Octopodes");
+        assert_eq!(scr.expand("<(s (low (cap animal)))>").unwrap(), "octopodes");
+    }
+
+    struct Pad;
+
+    impl Transform for Pad {
+        fn apply(&self, text: &str) -> String {
+            text.to_string()
+        }
+
+        fn apply_with_args(&self, args: &[String], text: &str) -> String {
+            let width = args[0].parse::<usize>().unwrap();
+            let fill = args[1].repeat(width.saturating_sub(text.len()));
+            format!("{}{}", fill, text)
+        }
+    }
+
+    #[test]
+    fn test_inline_choice() {
+        let mut scr = Scribe::default();
+        scr.cognate("ship").group_from_rules(&["sloop"]).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let text = scr
+                .expand_with_rng("a <{<^0>tiny|big <ship>}>", Context::default(), &mut rng)
+                .unwrap();
+            assert_eq!(text, "a big sloop");
+        }
+        let texts = (0..50)
+            .map(|seed| scr.expand_seeded("<{a|b|c}>", Context::default(), seed))
+            .collect::<Result<std::collections::HashSet<_>, _>>()
+            .unwrap();
+        assert_eq!(texts.len(), 3);
+    }
+
+    #[test]
+    fn test_optional_segments() {
+        let mut scr = Scribe::default();
+        scr.cognate("adj").group_from_rules(&["old"]).unwrap();
+        macro_rules! expands_to {
+            ($rule: expr, $text: expr) => {
+                assert_eq!(scr.expand($rule).unwrap(), $text);
+            };
+        }
+        expands_to!("the <adj?100> ship", "the old ship");
+        expands_to!("the <adj?0> ship", "the ship");
+        expands_to!("<adj?0> ships", "ships");
+        expands_to!("a ship <adj?0>", "a ship");
+        expands_to!("a ship <adj?0>, ahoy", "a ship, ahoy");
+        expands_to!("the <adj?0> <adj?0> <{|<^0>big}> ship", "the ship");
+        expands_to!("the<[ <adj>]?0> ship", "the ship");
+        expands_to!("the<[ <adj>]?100> ship", "the old ship");
+        let texts = (0..50)
+            .map(|seed| scr.expand_seeded("<adj?>", Context::default(), seed))
+            .collect::<Result<std::collections::HashSet<_>, _>>()
+            .unwrap();
+        assert_eq!(texts.len(), 2);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut scr = Scribe::default();
+        scr.cognate("good")
+            .group_from_rules(&["silver", "emeralds", "whale oil"])
+            .unwrap();
+        scr.cognate("root")
+            .group_from_rules(&["Laden with <good*5 last=\" and \" unique>."])
+            .unwrap();
+        for seed in 0..10 {
+            let text = scr
+                .expand_seeded("<good*2-3 sep=\"; \" unique>", Context::default(), seed)
+                .unwrap();
+            let items = text.split("; ").collect::<Vec<_>>();
+            assert!(items.len() == 2 || items.len() == 3);
+            assert!(items
+                .iter()
+                .all(|item| !text.replacen(item, "", 1).contains(item)));
+
+            // Three unique goods are joined as a list, even though five were asked for.
+            let (text, tree) = scr
+                .gen_traced_with_rng("root", Context::default(), &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert!(text.starts_with("Laden with "));
+            assert_eq!(text.matches(", ").count(), 1);
+            assert_eq!(text.matches(" and ").count(), 1);
+            assert_eq!(tree.children.len(), 3);
+            for child in &tree.children {
+                assert_eq!(child.text(&text), child.rule);
+            }
+        }
+        assert_eq!(scr.expand("<good*0>").unwrap(), "");
+        assert!(!scr.expand("<good*2 sep=\"\">").unwrap().is_empty());
+        // Counts which would overflow are rejected when parsed.
+        assert!(matches!(
+            scr.expand("<good*18446744073709551615>"),
+            Err(AnnalsError::InvalidRule { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tag_predicates() {
+        let scr = Scribe::from_str(
+            r#"
+cognates:
+  gun:
+    name: gun
+    groups:
+      - tags: { crew: ">=200", era: "!old" }
+        rules: [cannon]
+      - tags: { crew: "<200", era: "old|ancient" }
+        rules: [culverin]
+      - tags: { crew: "!*" }
+        rules: [nothing]
+"#,
+        )
+        .unwrap();
+        let gen = |tags: &[(&str, &str)]| {
+            let tags = tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            scr.gen_with("gun", Context::with_tags(tags))
+        };
+        assert_eq!(gen(&[("crew", "250"), ("era", "new")]).unwrap(), "cannon");
+        assert_eq!(
+            gen(&[("crew", "20"), ("era", "ancient")]).unwrap(),
+            "culverin"
+        );
+        assert!(gen(&[("crew", "250"), ("era", "old")]).is_err());
+        let text = gen(&[("era", "old")]).unwrap();
+        assert!(text == "culverin" || text == "nothing");
+
+        // Predicates are not merged into the Context.
+        let mut context = Context::default();
+        context.merge_from_group(scr.cognates["gun"].iter_groups().next().unwrap());
+        assert!(context.tags.is_empty());
+
+        // Tags are parsed once, but changing one reparses them.
+        let mut group = Group::new();
+        group.set_tag("crew", ">=200");
+        let context = Context::with_tags(vec![("crew".into(), "12".into())].into_iter().collect());
+        assert!(!context.accept_strict(&group));
+        group.set_tag("crew", "<200");
+        assert!(context.accept_strict(&group));
+    }
+
+    #[test]
+    fn test_salience() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: ship
+  groups:
+    - rules: [sloop, brig, frigate, corvette]
+    - tags: { size: big }
+      rules: [galleon]
+    - tags: { size: big, era: old }
+      rules: [carrack]
+- name: boat
+  selection: salient
+  groups:
+    - rules: [dinghy]
+    - tags: { size: big }
+      rules: [barge]
+"#,
+        )
+        .unwrap();
+        let mut tags = HashMap::new();
+        tags.insert("size".to_string(), "big".to_string());
+        tags.insert("era".to_string(), "old".to_string());
+        let ships = |scr: &Scribe| {
+            (0..50)
+                .map(|seed| scr.gen_seeded("ship", Context::with_tags(tags.clone()), seed))
+                .collect::<Result<std::collections::HashSet<_>, _>>()
+                .unwrap()
+        };
+        assert!(ships(&scr).contains("sloop"));
+        for _ in 0..10 {
+            assert_eq!(
+                scr.gen_with("boat", Context::with_tags(tags.clone()))
+                    .unwrap(),
+                "barge"
+            );
+        }
+        scr.set_selection(Selection::Salient);
+        assert_eq!(ships(&scr).into_iter().collect::<Vec<_>>(), vec!["carrack"]);
+        scr.set_selection(Selection::Weighted);
+        assert_eq!(ships(&scr).len(), 6);
+    }
+
+    #[test]
+    fn test_tag_scope() {
+        let grammar = |scope: &str| {
+            let mut scr = Scribe::default();
+            scr.load_cognates_str(&format!(
+                r#"
+- name: class
+  groups:
+    - tags: {{ weight: heavy, era: old }}
+      scope: {}
+      rules: [cruiser]
+- name: gun
+  groups:
+    - tags: {{ weight: heavy }}
+      rules: [big guns]
+    - tags: {{ weight: light }}
+      rules: [small guns]
+- name: root
+  groups:
+    - rules: ["<class> with <gun>"]
+"#,
+                scope
+            ))
+            .unwrap();
+            scr
+        };
+        let guns = |scr: &Scribe| {
+            (0..50)
+                .map(|seed| {
+                    let mut context = Context::default();
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let text = scr.expand_cognate("root", &mut context, &mut rng).unwrap();
+                    (text, context.tags)
+                })
+                .collect::<Vec<_>>()
+        };
+        for (text, tags) in guns(&grammar("propagate")) {
+            assert_eq!(text, "cruiser with big guns");
+            assert_eq!(tags["era"], "old");
+        }
+        let local = guns(&grammar("local"));
+        assert!(local
+            .iter()
+            .any(|(text, _)| text == "cruiser with small guns"));
+        assert!(local.iter().all(|(_, tags)| !tags.contains_key("era")));
+        for (text, tags) in guns(&grammar("{export: [weight]}")) {
+            assert_eq!(text, "cruiser with big guns");
+            assert!(!tags.contains_key("era"));
+        }
+    }
+
+    #[test]
+    fn test_tag_tokens() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: gun
+  groups:
+    - tags: { weight: heavy }
+      rules: [big guns]
+    - tags: { weight: light }
+      rules: [small guns]
+"#,
+        )
+        .unwrap();
+        let mut context = Context::default();
+        let text = scr
+            .expand_with_rng(
+                "a sloop <+weight=light>with <gun>",
+                context.clone(),
+                &mut thread_rng(),
+            )
+            .unwrap();
+        assert_eq!(text, "a sloop with small guns");
+        context.set("weight", "heavy");
+        let text = scr
+            .expand_with("<gun>, then <-weight> <+weight=light> <gun>", context)
+            .unwrap();
+        assert_eq!(text, "big guns, then small guns");
+    }
+
+    #[test]
+    fn test_group_bind() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: captain
+  groups:
+    - bind: true
+      rules: [Ahab, Nemo, Bligh, Hook, Haddock, Flint]
+- name: crew
+  groups:
+    - rules: [Ishmael, Queequeg, Starbuck, Stubb, Flask, Pip]
+- name: root
+  groups:
+    - rules: ["<captain>/<captain>/<crew>/<crew>"]
+"#,
+        )
+        .unwrap();
+        let mut differ = false;
+        for seed in 0..20 {
+            let text = scr.gen_seeded("root", Context::default(), seed).unwrap();
+            let names = text.split('/').collect::<Vec<_>>();
+            assert_eq!(names[0], names[1]);
+            differ |= names[2] != names[3];
+        }
+        assert!(differ);
+        // The binding lasts only for the rule which made it.
+        scr.cognate("hail")
+            .group_from_rules(&["<captain>"])
+            .unwrap();
+        let differ = (0..20).any(|seed| {
+            let text = scr
+                .expand_seeded("<hail>/<hail>", Context::default(), seed)
+                .unwrap();
+            let names = text.split('/').collect::<Vec<_>>();
+            names[0] != names[1]
+        });
+        assert!(differ);
+    }
+
+    #[test]
+    fn test_command_templates() {
+        let mut scr = Scribe::default();
+        scr.cognate("ship").group_from_rules(&["sloop"]).unwrap();
+        scr.cognate("adj").group_from_rules(&["old"]).unwrap();
+        assert_eq!(
+            scr.expand("<(title the <adj> <ship>)>").unwrap(),
+            "The Old Sloop"
+        );
+        assert_eq!(
+            scr.expand("<(cap <(an adj)> <ship>)>").unwrap(),
+            "An old sloop"
+        );
+        assert_eq!(
+            scr.expand("<(truncate 6 the <adj> <ship>)>").unwrap(),
+            "the ol"
+        );
+    }
+
+    #[test]
+    fn test_custom_transform() {
+        let grammar = "[{name: year, groups: [{rules: [\"Year <(roman num)>\"]}]}, \
+                       {name: num, groups: [{rules: [\"1805\"]}]}]";
+        let mut scr = Scribe::default();
+        match scr.load_cognates_str(grammar) {
+            Err(AnnalsError::InvalidRule { err, expr }) => {
+                assert_eq!(err, ParseError::UnknownCommand(6, 11));
+                assert_eq!(expr, "Year <(roman num)>");
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(scr.iter().next().is_none());
+
+        scr.register_transform("roman", |text: &str| match text {
+            "1805" => "MDCCCV".to_string(),
+            _ => text.to_string(),
+        });
+        scr.load_cognates_str(grammar).unwrap();
+        assert_eq!(scr.gen("year").unwrap(), "Year MDCCCV");
+        assert_eq!(scr.expand("<(cap (roman num))>").unwrap(), "MDCCCV");
+        assert!(scr.expand("<(shout num)>").is_err());
+
+        scr.register_transform("pad", Pad);
+        assert_eq!(scr.expand("<(pad 6 \"*\" num)>").unwrap(), "**1805");
+    }
+
+    #[test]
+    fn test_load_project() {
+        let mut scr = Scribe::default();
+        scr.load_project("texts/project/main.yml").unwrap();
+        let text = scr.gen("root").unwrap();
+        assert!(text.starts_with("The HMS ") || text.starts_with("The SS "));
+        assert!(text.ends_with("Ahab.") || text.ends_with("Nemo."));
+
+        let mut scr = Scribe::default();
+        match scr.load_project("texts/project/broken/main.yml") {
+            Err(err) => assert!(err.to_string().contains("bad.yml: ")),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(scr.iter().next().is_none());
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let mut scr = Scribe::default();
+        scr.load_project("texts/project/fleet/main.yml").unwrap();
+        assert_eq!(
+            scr.gen("root").unwrap(),
+            "HMS Victory and SS Great Britain, master Smith"
+        );
+        assert_eq!(scr.gen("merchant::name").unwrap(), "Great Britain");
+        assert!(scr.validate(Some("root")).is_empty());
+        match scr.gen("name") {
+            Err(AnnalsError::UnknownCognate { name }) => assert_eq!(name, "name"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // A top-level Cognate is used where the namespace lacks one.
+        scr.cognate("name")
+            .group_from_rules(&["Cutty Sark"])
+            .unwrap();
+        assert_eq!(scr.gen("name").unwrap(), "Cutty Sark");
+        assert_eq!(scr.gen("navy::ship").unwrap(), "HMS Victory");
+    }
+
+    #[test]
+    fn test_merge_policy() {
+        let first = "[{name: ship, groups: [{rules: [sloop]}]}]";
+        let second = "[{name: ship, groups: [{rules: [brig]}]}, \
+                      {name: hull, groups: [{rules: [oak]}]}]";
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(first).unwrap();
+        scr.load_cognates_str(second).unwrap();
+        assert_eq!(scr.gen("ship").unwrap(), "brig");
+
+        let mut scr = Scribe::default();
+        scr.set_merge_policy(MergePolicy::Error);
+        scr.load_cognates_str(first).unwrap();
+        match scr.load_cognates_str(second) {
+            Err(AnnalsError::DuplicateCognate { name }) => assert_eq!(name, "ship"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(scr.gen("hull").is_err());
+        match scr.load_cognates_str("[{name: a, groups: []}, {name: a, groups: []}]") {
+            Err(AnnalsError::DuplicateCognate { name }) => assert_eq!(name, "a"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let mut scr = Scribe::default();
+        scr.set_merge_policy(MergePolicy::Append);
+        scr.load_cognates_str(first).unwrap();
+        scr.load_cognates_str(second).unwrap();
+        assert_eq!(scr.cognates["ship"].rules_count(), 2);
+    }
+
+    #[test]
+    fn test_extend() {
+        let base = "[{name: animal, groups: [{rules: [whale]}]}]";
+        let module = "[{name: animal, extend: true, groups: [{rules: [kraken]}]}, \
+                      {name: sea, groups: [{rules: [deep]}]}]";
+        let mut scr = Scribe::default();
+        scr.set_merge_policy(MergePolicy::Error);
+        scr.load_cognates_str(base).unwrap();
+        scr.load_cognates_str(module).unwrap();
+        assert_eq!(scr.cognates["animal"].rules_count(), 2);
+        assert_eq!(scr.cognates["animal"].len(), 2);
+        assert!(!scr.cognates["animal"].extend);
+
+        let mut other = Scribe::default();
+        other.register_transform("shout", |text: &str| text.to_uppercase());
+        other
+            .load_cognates_str("[{name: sky, groups: [{rules: [<(shout sea)>]}]}]")
+            .unwrap();
+        match scr.merge(other) {
+            Err(AnnalsError::InvalidRule { expr, .. }) => assert_eq!(expr, "<(shout sea)>"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        let mut other = Scribe::default();
+        other.load_cognates_str(module).unwrap();
+        match scr.merge(other) {
+            Err(AnnalsError::DuplicateCognate { name }) => assert_eq!(name, "sea"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(scr.cognates["animal"].rules_count(), 2);
+
+        let mut other = Scribe::default();
+        other.load_cognates_str(module).unwrap();
+        scr.set_merge_policy(MergePolicy::Override);
+        scr.merge(other).unwrap();
+        assert_eq!(scr.cognates["animal"].rules_count(), 3);
+        assert_eq!(scr.gen("sea").unwrap(), "deep");
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut base = Scribe::default();
+        base.load_cognates_str(
+            "[{name: animal, groups: [{tags: {size: big}, rules: [whale]}, \
+                                      {tags: {size: small}, rules: [mouse]}]}, \
+              {name: sea, groups: [{rules: [deep]}]}, \
+              {name: root, groups: [{rules: [<animal> in the <sea>]}]}]",
+        )
+        .unwrap();
+        let base = Arc::new(base);
+
+        let mut layer = Scribe::overlay(base.clone());
+        layer
+            .load_cognates_str("[{name: animal, extend: true, groups: [{rules: [kraken]}]}]")
+            .unwrap();
+        layer.hide_group("animal", 0);
+        layer.hide_cognate("sea");
+        assert_eq!(layer.view("animal").unwrap().groups.len(), 2);
+        let big = Context::with_tags(vec![("size".into(), "big".into())].into_iter().collect());
+        assert_eq!(layer.gen_with("animal", big.clone()).unwrap(), "kraken");
+        assert_eq!(base.gen_with("animal", big).unwrap(), "whale");
+        match layer.gen("sea") {
+            Err(AnnalsError::UnknownCognate { name }) => assert_eq!(name, "sea"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(
+            layer.validate(Some("root")),
+            vec![Diagnostic::UnknownCognate {
+                cognate: "root".into(),
+                rule: "<animal> in the <sea>".into(),
+                name: "sea".into(),
+            }]
+        );
+
+        // A Cognate of the overlay replaces a hidden one.
+        layer.set_merge_policy(MergePolicy::Error);
+        layer
+            .load_cognates_str("[{name: sea, groups: [{rules: [shallows]}]}]")
+            .unwrap();
+        assert!(layer
+            .load_cognates_str("[{name: root, groups: [{rules: [<sea>]}]}]")
+            .is_err());
+        assert_eq!(layer.gen("sea").unwrap(), "shallows");
+        assert_eq!(base.gen("sea").unwrap(), "deep");
+
+        // Overlays can be stacked, and appending extends the layers below.
+        let layer = Arc::new(layer);
+        let mut top = Scribe::overlay(layer.clone());
+        top.set_merge_policy(MergePolicy::Append);
+        top.load_cognates_str("[{name: animal, groups: [{rules: [squid]}]}]")
+            .unwrap();
+        top.hide_group("animal", 1);
+        let animal = top.view("animal").unwrap();
+        assert_eq!(animal.groups.len(), 2);
+        assert_eq!(animal.groups[0].rules[0].literal(), "mouse");
+        assert_eq!(animal.groups[1].rules[0].literal(), "squid");
+        assert_eq!(layer.view("animal").unwrap().groups.len(), 2);
+        assert!(top.validate(Some("root")).is_empty());
+
+        // Saving an overlay writes everything visible through it.
+        let path = std::env::temp_dir().join("annals-overlay.yml");
+        let path = path.to_str().unwrap();
+        let check = |saved: Scribe| {
+            assert_eq!(saved.iter().count(), 3);
+            assert_eq!(saved.gen("sea").unwrap(), "shallows");
+            assert_eq!(saved.view("animal").unwrap().groups.len(), 2);
+        };
+        top.save(path).unwrap();
+        check(Scribe::from(path).unwrap());
+        top.save_cognates(path).unwrap();
+        let mut saved = Scribe::default();
+        saved.load_cognates(path).unwrap();
+        check(saved);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_formats() {
+        let mut scr = Scribe::default();
+        scr.load_cognates("texts/hms.yml").unwrap();
+        for extension in ["json", "toml", "ron"].iter() {
+            let path = std::env::temp_dir().join(format!("annals-formats.{}", extension));
+            let path = path.to_str().unwrap();
+            match scr.save_cognates(path) {
+                Ok(()) => {
+                    let mut loaded = Scribe::default();
+                    loaded.load_cognates(path).unwrap();
+                    std::fs::remove_file(path).unwrap();
+                    assert_eq!(loaded.iter().count(), scr.iter().count());
+                    assert!(loaded.validate(Some("root")).is_empty());
+                }
+                Err(AnnalsError::FormatDisabled { feature }) => assert_eq!(&feature, extension),
+                Err(err) => panic!("Failed to save {}: {}", path, err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut scr = Scribe::default();
+        scr.cognate("a").group_from_rules(&["<a>"]).unwrap();
+        match scr.gen("a") {
+            Err(AnnalsError::DepthLimitExceeded { name, limit }) => {
+                assert_eq!(name, "a");
+                assert_eq!(limit, Limits::default().max_depth.unwrap());
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        scr.cognate("wide")
+            .group_from_rules(&["<leaf><leaf><leaf><leaf>"])
+            .unwrap();
+        scr.cognate("leaf").group_from_rules(&["leaf"]).unwrap();
+        assert_eq!(scr.gen("wide").unwrap(), "leafleafleafleaf");
+        scr.set_limits(Limits {
+            max_expansions: Some(4),
+            ..Limits::default()
+        });
+        match scr.gen("wide") {
+            Err(AnnalsError::ExpansionLimitExceeded { limit: 4 }) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        scr.set_limits(Limits {
+            max_output: Some(10),
+            ..Limits::default()
+        });
+        match scr.gen("wide") {
+            Err(AnnalsError::OutputLimitExceeded { limit: 10 }) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // Each item of a repetition counts as an expansion, even if it only
+        // reads a variable.
+        scr.set_limits(Limits {
+            max_expansions: Some(100),
+            ..Limits::default()
+        });
+        assert_eq!(scr.expand("<[v:leaf]><v*99 sep=\"\">").unwrap().len(), 396);
+        match scr.expand("<[v:leaf]><v*100 sep=\"\">") {
+            Err(AnnalsError::ExpansionLimitExceeded { limit: 100 }) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible() {
+        let mut scr = Scribe::default();
+        scr.cognate("animal")
+            .group_from_rules(&["elephant", "whale", "mouse", "milk snake"])
+            .unwrap();
+        scr.cognate("root")
+            .group_from_rules(&["<animal> and <animal>, <#1-100> times"])
+            .unwrap();
+        for seed in 0..16 {
+            let first = scr.gen_seeded("root", Context::default(), seed).unwrap();
+            let second = scr.gen_seeded("root", Context::default(), seed).unwrap();
+            assert_eq!(first, second);
+        }
+        let mut rng = StdRng::seed_from_u64(7);
+        let from_rng = scr
+            .expand_with_rng("<root>", Context::default(), &mut rng)
+            .unwrap();
+        assert_eq!(
+            from_rng,
+            scr.expand_seeded("<root>", Context::default(), 7).unwrap()
+        );
+    }
 }
@@ -0,0 +1,47 @@
+/// Bounds on the work done generating a single text, so that grammars from
+/// untrusted sources cannot exhaust the stack or memory.
+///
+/// ```
+/// use annals::{limits::Limits, Scribe};
+///
+/// let mut scribe = Scribe::default();
+/// scribe.set_limits(Limits {
+///     max_expansions: Some(1000),
+///     max_output: Some(64 * 1024),
+///     ..Limits::default()
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// Maximum nesting depth of Cognate expansions.
+    pub max_depth: Option<usize>,
+    /// Maximum number of Cognate expansions in one text. Each item of a
+    /// repetition, such as `<good*3>`, counts as an expansion too.
+    pub max_expansions: Option<usize>,
+    /// Maximum length of the text, or any part of it, in bytes.
+    pub max_output: Option<usize>,
+}
+
+impl Limits {
+    /// Create a set of limits which bounds nothing. Self-referential grammars
+    /// will overflow the stack.
+    pub fn none() -> Self {
+        Limits {
+            max_depth: None,
+            max_expansions: None,
+            max_output: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    /// The default limits bound only nesting depth, well within the stack
+    /// available to a spawned thread.
+    fn default() -> Self {
+        Limits {
+            max_depth: Some(128),
+            max_expansions: None,
+            max_output: None,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::Value;
+
+use crate::cognate::Cognate;
+use crate::error::AnnalsError;
+use crate::format::Format;
+use crate::parse::is_cognate_name;
+
+/// Attach the path of the file being loaded to an error.
+pub(crate) fn in_file(path: &Path, err: AnnalsError) -> AnnalsError {
+    match err {
+        AnnalsError::InFile { .. } => err,
+        err => AnnalsError::InFile {
+            path: path.display().to_string(),
+            err: Box::new(err),
+        },
+    }
+}
+
+/// Qualify a name with a namespace, if there is one.
+fn qualify(namespace: &str, name: &str) -> String {
+    match namespace {
+        "" => name.to_string(),
+        namespace => format!("{}::{}", namespace, name),
+    }
+}
+
+/// An entry in the list of a grammar file.
+#[derive(Debug, PartialEq)]
+enum Entry {
+    /// Load the files named by these patterns, in the given namespace.
+    Include(Vec<String>, Option<String>),
+    /// Place the Cognates of this file in a namespace.
+    Namespace(String),
+    /// A Cognate, yet to be deserialized.
+    Cognate(Value),
+}
+
+/// Read an entry of a grammar file.
+fn parse_entry(entry: Value) -> Result<Entry, AnnalsError> {
+    let map = match entry.as_mapping() {
+        Some(map) => map,
+        None => return Ok(Entry::Cognate(entry)),
+    };
+    let namespace = match map.get(&Value::from("namespace")) {
+        Some(Value::String(namespace)) if is_cognate_name(namespace) => Some(namespace.clone()),
+        Some(other) => {
+            return Err(AnnalsError::InvalidNamespace {
+                name: serde_yaml::to_string(other).unwrap_or_default(),
+            })
+        }
+        None => None,
+    };
+    let include = match map.get(&Value::from("include")) {
+        Some(include) => include,
+        None => {
+            return match namespace {
+                Some(namespace) if map.len() == 1 => Ok(Entry::Namespace(namespace)),
+                _ => Ok(Entry::Cognate(entry)),
+            }
+        }
+    };
+    let invalid = || AnnalsError::InvalidInclude {
+        pattern: serde_yaml::to_string(include).unwrap_or_default(),
+    };
+    if map.len() != 1 + namespace.is_some() as usize {
+        return Err(invalid());
+    }
+    let patterns = match include {
+        Value::String(pattern) => vec![pattern.clone()],
+        Value::Sequence(seq) => seq
+            .iter()
+            .map(|pattern| pattern.as_str().map(str::to_string).ok_or_else(invalid))
+            .collect::<Result<_, _>>()?,
+        _ => return Err(invalid()),
+    };
+    Ok(Entry::Include(patterns, namespace))
+}
+
+/// Expand an include pattern, relative to `dir`, into the paths it names.
+/// Glob patterns are expanded in sorted order, and may match nothing.
+fn expand(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, AnnalsError> {
+    let joined = dir.join(pattern);
+    if !pattern.contains(|ch| "*?[".contains(ch)) {
+        return Ok(vec![joined]);
+    }
+    let invalid = || AnnalsError::InvalidInclude {
+        pattern: pattern.to_string(),
+    };
+    let paths = glob::glob(joined.to_str().ok_or_else(invalid)?).map_err(|_| invalid())?;
+    let mut paths = paths
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| AnnalsError::IOError(err.into()))?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Loads Cognates from a grammar file and every file it includes.
+///
+/// Files may be in any enabled [`Format`](../format/enum.Format.html), chosen
+/// by their extensions.
+///
+/// Alongside Cognates, the list in a file may hold entries of the form
+/// `include: path` or `include: [path, ...]`, where paths are relative to the
+/// including file and may be glob patterns such as `ships/*.yml`. Included
+/// files are loaded in place of the entry. Each file is loaded at most once,
+/// so files may include each other freely.
+///
+/// A file may place its Cognates in a namespace with a `namespace: name`
+/// entry, so that a Cognate `class` is loaded as `name::class`. An include
+/// entry may also give a `namespace`, nested within that of the including
+/// file, for the files it loads which do not declare their own; otherwise
+/// they share the including file's namespace.
+#[derive(Debug, Default)]
+pub(crate) struct Loader {
+    visited: HashSet<PathBuf>,
+    /// Cognates loaded so far, with the file each came from.
+    pub cognates: Vec<(PathBuf, Cognate)>,
+}
+
+impl Loader {
+    /// Load a file and, recursively, the files it includes. Errors name the
+    /// file in which they occurred.
+    pub fn load(&mut self, path: &Path) -> Result<(), AnnalsError> {
+        self.load_in(path, "")
+    }
+
+    /// Load a file, placing its Cognates in `namespace` unless it declares
+    /// its own.
+    fn load_in(&mut self, path: &Path, namespace: &str) -> Result<(), AnnalsError> {
+        let path = path
+            .canonicalize()
+            .map_err(|err| in_file(path, err.into()))?;
+        if !self.visited.insert(path.clone()) {
+            return Ok(());
+        }
+        self.load_file(&path, namespace)
+            .map_err(|err| in_file(&path, err))
+    }
+
+    /// Load a file which has not been loaded before.
+    fn load_file(&mut self, path: &Path, namespace: &str) -> Result<(), AnnalsError> {
+        let data = fs::read_to_string(path)?;
+        let entries = Format::from_path(path)
+            .deserialize_list::<Value>(&data)?
+            .into_iter()
+            .map(parse_entry)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut declared = entries.iter().filter_map(|entry| match entry {
+            Entry::Namespace(namespace) => Some(namespace.as_str()),
+            _ => None,
+        });
+        let namespace = match (declared.next(), declared.next()) {
+            (_, Some(second)) => {
+                return Err(AnnalsError::InvalidNamespace {
+                    name: second.to_string(),
+                })
+            }
+            (Some(declared), None) => declared.to_string(),
+            (None, None) => namespace.to_string(),
+        };
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for entry in entries {
+            match entry {
+                Entry::Include(patterns, inner) => {
+                    let inner = match inner {
+                        Some(inner) => qualify(&namespace, &inner),
+                        None => namespace.clone(),
+                    };
+                    for pattern in patterns {
+                        for include in expand(dir, &pattern)? {
+                            self.load_in(&include, &inner)?;
+                        }
+                    }
+                }
+                Entry::Namespace(_) => (),
+                Entry::Cognate(value) => {
+                    let mut cognate: Cognate = serde_yaml::from_value(value)?;
+                    cognate.name = qualify(&namespace, &cognate.name);
+                    self.cognates.push((path.to_path_buf(), cognate));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_project() {
+        let mut loader = Loader::default();
+        loader.load(Path::new("texts/project/main.yml")).unwrap();
+        let names = loader
+            .cognates
+            .iter()
+            .map(|(_, cog)| cog.name.as_str())
+            .collect::<Vec<_>>();
+        // Files are loaded in order of inclusion, and only once.
+        assert_eq!(names, vec!["ship", "hull", "name", "captain", "root"]);
+        assert!(loader.cognates[0].0.ends_with("ships/hulls.yml"));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let mut loader = Loader::default();
+        match loader.load(Path::new("texts/project/broken/main.yml")) {
+            Err(AnnalsError::InFile { path, .. }) => assert!(path.ends_with("broken/bad.yml")),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match Loader::default().load(Path::new("texts/project/missing.yml")) {
+            Err(AnnalsError::InFile { path, err }) => {
+                assert_eq!(path, "texts/project/missing.yml");
+                assert!(matches!(*err, AnnalsError::IOError(_)));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let mut loader = Loader::default();
+        loader
+            .load(Path::new("texts/project/fleet/main.yml"))
+            .unwrap();
+        let names = loader
+            .cognates
+            .iter()
+            .map(|(_, cog)| cog.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "navy::ship",
+                "navy::name",
+                "merchant::ship",
+                "merchant::name",
+                "root",
+                "captain"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let entry = |yaml: &str| parse_entry(serde_yaml::from_str(yaml).unwrap());
+        assert_eq!(
+            entry("include: a.yml").unwrap(),
+            Entry::Include(vec!["a.yml".into()], None)
+        );
+        assert_eq!(
+            entry("{include: [a.yml, b/*.yml], namespace: b}").unwrap(),
+            Entry::Include(vec!["a.yml".into(), "b/*.yml".into()], Some("b".into()))
+        );
+        assert_eq!(
+            entry("namespace: navy::ships").unwrap(),
+            Entry::Namespace("navy::ships".into())
+        );
+        assert!(matches!(
+            entry("{include: a.yml, name: x}"),
+            Err(AnnalsError::InvalidInclude { .. })
+        ));
+        assert!(matches!(
+            entry("include: 3"),
+            Err(AnnalsError::InvalidInclude { .. })
+        ));
+        assert!(matches!(
+            entry("namespace: \"a b\""),
+            Err(AnnalsError::InvalidNamespace { .. })
+        ));
+        assert!(matches!(entry("name: include"), Ok(Entry::Cognate(_))));
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens when a Cognate is loaded into a
+/// [`Scribe`](../struct.Scribe.html) which already has one of the same name.
+///
+/// ```
+/// use annals::{merge::MergePolicy, Scribe};
+///
+/// let mut scribe = Scribe::default();
+/// scribe.set_merge_policy(MergePolicy::Error);
+/// scribe
+///     .load_cognates_str("[{name: ship, groups: [{rules: [sloop]}]}]")
+///     .unwrap();
+/// assert!(scribe
+///     .load_cognates_str("[{name: ship, groups: [{rules: [brig]}]}]")
+///     .is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergePolicy {
+    /// Replace the existing Cognate.
+    #[default]
+    Override,
+    /// Fail with `AnnalsError::DuplicateCognate`, loading nothing.
+    Error,
+    /// Add the new Cognate's Groups to those of the existing one.
+    Append,
+}
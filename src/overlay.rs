@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::cognate::namespace_of;
+use crate::group::Group;
+use crate::selection::Selection;
+
+/// A Cognate as seen through a [`Scribe`](../struct.Scribe.html) and the
+/// Scribes it overlays: the Groups of every layer which extends it, less those
+/// hidden by a higher layer. Serializes as a Cognate holding all of them.
+#[derive(Debug, Serialize)]
+pub(crate) struct View<'a> {
+    pub name: &'a str,
+    /// The selection mode set by the highest layer which sets one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection: Option<Selection>,
+    pub groups: Vec<&'a Group>,
+}
+
+impl<'a> View<'a> {
+    /// Get the namespace of the Cognate.
+    pub fn namespace(&self) -> &'a str {
+        namespace_of(self.name)
+    }
+
+    /// Drop the Groups at the given indices.
+    pub fn hide(&mut self, indices: &[usize]) {
+        self.groups = self
+            .groups
+            .drain(..)
+            .enumerate()
+            .filter(|(index, _)| !indices.contains(index))
+            .map(|(_, group)| group)
+            .collect();
+    }
+}
+
+/// The Cognates visible through a Scribe and the Scribes it overlays,
+/// serialized in the same form as a Scribe.
+#[derive(Serialize)]
+pub(crate) struct Flattened<'a> {
+    pub cognates: BTreeMap<&'a str, View<'a>>,
+}
@@ -8,6 +8,13 @@ pub enum Command {
     Lowercase,
     Titlecase,
     IndefiniteArticle,
+    Plural,
+    /// Keep at most this many characters.
+    Truncate(usize),
+    /// A command registered by the application, with its literal arguments
+    /// and the position of its name in the rule so that unregistered commands
+    /// can be reported.
+    Custom(String, Vec<String>, usize, usize),
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,9 +23,23 @@ pub enum Token {
     NonTerminal(String),
     StickyNonTerminal(String),
     Binding(String),
-    Expression(Command, Box<Token>),
+    Expression(Command, Vec<Token>),
     Range(usize, usize),
-    VariableAssignment(String, String),
+    VariableAssignment(Vec<(String, String)>, Option<Box<Token>>),
+    Weight(f64),
+    PluralForm(String),
+    Choice(Vec<(Vec<Token>, f64)>),
+    Optional(f64, Vec<Token>),
+    SetTag(String, String),
+    ClearTag(String),
+    Repeat {
+        token: Box<Token>,
+        min: usize,
+        max: usize,
+        sep: String,
+        last: Option<String>,
+        unique: bool,
+    },
 }
 
 /// Make a Token::Literal from a string slice.
@@ -27,57 +48,396 @@ fn make_literal(expr: &str, beg: usize, end: usize) -> Token {
     Token::Literal(lit.replace("\\<", "<").replace("\\>", ">"))
 }
 
+/// Split a literal argument, either a finite number or a double-quoted string,
+/// from the front of a command's operands. Returns the argument and the remaining
+/// operands, or `None` if the operands do not begin with an argument.
+fn split_argument(operands: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = operands.strip_prefix('"') {
+        let close = quoted.find('"')?;
+        return Some((quoted[..close].to_string(), &quoted[close + 1..]));
+    }
+    let len = operands.find(char::is_whitespace)?;
+    let word = &operands[..len];
+    match word.parse::<f64>() {
+        Ok(num) if num.is_finite() => Some((word.to_string(), &operands[len..])),
+        _ => None,
+    }
+}
+
+/// Check if a string contains an unescaped '<'.
+fn has_substitution(expr: &str) -> bool {
+    let mut prev_glyph = ' ';
+    for glyph in expr.chars() {
+        if glyph == '<' && prev_glyph != '\\' {
+            return true;
+        }
+        prev_glyph = glyph;
+    }
+    false
+}
+
+/// Parse the body of a command: either a single name or command, as in
+/// `(cap animal)`, or a template, as in `(cap the <animal>)`.
+fn parse_cmd_body(body: &str, beg: usize, end: usize) -> Result<Vec<Token>, ParseError> {
+    if has_substitution(body) {
+        make_tokens(body, beg)
+    } else {
+        Ok(vec![validate_substitution_expr(body, beg, end)?])
+    }
+}
+
 /// Parse a Token::Expression from a string slice.
 fn parse_cmd_expr(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
     if expr.len() == 2 {
         return Err(ParseError::ZeroLengthSubst(beg + 1, end));
     }
     let expr = &expr[1..expr.len() - 1];
-    let (cmd_str, tok_str) = match expr.find(' ') {
+    let (cmd_str, mut operands) = match expr.find(' ') {
         Some(first_space) => expr.split_at(first_space),
         None => return Err(ParseError::InvalidExpression(beg, end)),
     };
+    operands = operands.trim_start();
+    // Only truncate and custom commands take arguments: for them, leading
+    // numbers and quoted strings are arguments, so long as something is left
+    // over for the body. Other commands keep them as part of the body.
+    let takes_args = !matches!(
+        cmd_str,
+        "cap"
+            | "capitalize"
+            | "low"
+            | "lowercase"
+            | "title"
+            | "titlecase"
+            | "a"
+            | "an"
+            | "s"
+            | "plural"
+    );
+    let mut args = vec![];
+    while let Some((arg, rest)) = split_argument(operands).filter(|_| takes_args) {
+        if rest.trim().is_empty() {
+            break;
+        }
+        args.push(arg);
+        operands = rest.trim_start();
+    }
+    let body = operands.trim_end();
+    if body.is_empty() {
+        return Err(ParseError::InvalidExpression(beg, end));
+    }
     let cmd = match cmd_str {
         "cap" | "capitalize" => Command::Capitalize,
         "low" | "lowercase" => Command::Lowercase,
         "title" | "titlecase" => Command::Titlecase,
         "a" | "an" => Command::IndefiniteArticle,
+        "s" | "plural" => Command::Plural,
+        "truncate" => match args.as_slice() {
+            [len] => match len.parse::<usize>() {
+                Ok(len) => Command::Truncate(len),
+                Err(_) => return Err(ParseError::InvalidExpression(beg, end)),
+            },
+            _ => return Err(ParseError::InvalidExpression(beg, end)),
+        },
+        _ if is_bare_name(cmd_str) => {
+            Command::Custom(cmd_str.to_string(), args.clone(), beg, beg + cmd_str.len())
+        }
         _ => return Err(ParseError::UnknownCommand(beg, beg + cmd_str.len())),
     };
-    let tok = validate_substitution_expr(tok_str.trim(), beg + cmd_str.len(), end)?;
-    Ok(Token::Expression(cmd, Box::new(tok)))
+    let body_beg = beg + 1 + expr.len() - operands.len();
+    let body = parse_cmd_body(body, body_beg, end)?;
+    Ok(Token::Expression(cmd, body))
 }
 
 /// Parse a range expression.
 fn parse_range(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
     if let Some(index) = expr.find('-') {
         let (l_str, u_str) = expr.split_at(index);
-        let lower = l_str.parse::<usize>();
-        let upper = u_str[1..].parse::<usize>();
-        if lower.is_err() || upper.is_err() {
-            return Err(ParseError::InvalidRange(beg, end));
-        }
-        return Ok(Token::Range(lower.unwrap(), upper.unwrap()));
+        // The upper bound is exclusive, so the range must not be empty.
+        return match (l_str.parse::<usize>(), u_str[1..].parse::<usize>()) {
+            (Ok(lower), Ok(upper)) if lower < upper => Ok(Token::Range(lower, upper)),
+            _ => Err(ParseError::InvalidRange(beg, end)),
+        };
     }
     Err(ParseError::InvalidRange(beg, end))
 }
 
-fn parse_variable(expr: &str, _beg: usize, _end: usize) -> Result<Token, ParseError> {
-    if let Some(index) = expr.find(':') {
-        let (vname, ntname) = expr.split_at(index);
-        Ok(Token::VariableAssignment(
-            vname.to_string(),
-            ntname.to_string(),
-        ))
+/// Parse a weight annotation.
+fn parse_weight(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    match expr.parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok(Token::Weight(weight)),
+        _ => Err(ParseError::InvalidWeight(beg, end)),
+    }
+}
+
+/// Remove any `<^N>` annotations from a sequence of Tokens, returning the
+/// weight given by the last of them, or 1.
+pub(crate) fn take_weight(tokens: &mut Vec<Token>) -> f64 {
+    let mut weight = 1.0;
+    tokens.retain(|tok| match tok {
+        Token::Weight(w) => {
+            weight = *w;
+            false
+        }
+        _ => true,
+    });
+    weight
+}
+
+/// Parse an inline choice between `|`-separated alternatives, each of which
+/// is a template which may carry a `<^N>` weight.
+fn parse_choice(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    let inner = match expr.strip_prefix('{').and_then(|e| e.strip_suffix('}')) {
+        Some(inner) => inner,
+        None => return Err(ParseError::InvalidExpression(beg, end)),
+    };
+    let mut choices = vec![];
+    let mut depth = 0;
+    let mut cbeg = 0;
+    let mut prev_glyph = ' ';
+    for (index, glyph) in inner.char_indices().chain(Some((inner.len(), '|'))) {
+        let escaped = prev_glyph == '\\';
+        prev_glyph = glyph;
+        match glyph {
+            '<' if !escaped => depth += 1,
+            '>' if !escaped => depth -= 1,
+            '|' if depth == 0 => {
+                let mut tokens = make_tokens(&inner[cbeg..index], beg + 1 + cbeg)?;
+                let weight = take_weight(&mut tokens);
+                choices.push((tokens, weight));
+                cbeg = index + 1;
+            }
+            _ => (),
+        }
+    }
+    if choices.iter().all(|(_, weight)| *weight <= 0.0) {
+        return Err(ParseError::InvalidWeight(beg, end));
+    }
+    Ok(Token::Choice(choices))
+}
+
+/// Split an expression with an optional `?N` suffix into the expression and
+/// the percentage, which is empty if not given.
+fn split_optional(expr: &str) -> Option<(&str, &str)> {
+    if expr.starts_with(|ch| "~^+".contains(ch)) {
+        return None;
+    }
+    let index = expr.rfind('?')?;
+    let (inner, percent) = (&expr[..index], &expr[index + 1..]);
+    if inner.is_empty() || !percent.chars().all(|ch| ch.is_ascii_digit() || ch == '.') {
+        return None;
+    }
+    Some((inner, percent))
+}
+
+/// Parse an expression which is expanded only with some probability, given
+/// as a percentage which defaults to 50. A template may be made optional by
+/// enclosing it in square brackets, as in `<[ <adjective>]?30>`.
+fn parse_optional(inner: &str, percent: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    let probability = match percent {
+        "" => 0.5,
+        _ => match percent.parse::<f64>() {
+            Ok(percent) if (0.0..=100.0).contains(&percent) => percent / 100.0,
+            _ => return Err(ParseError::InvalidExpression(beg, end)),
+        },
+    };
+    let tokens = match inner.strip_prefix('[').and_then(|e| e.strip_suffix(']')) {
+        Some(template) if has_substitution(template) => make_tokens(template, beg + 1)?,
+        _ => vec![validate_substitution_expr(inner, beg, beg + inner.len())?],
+    };
+    Ok(Token::Optional(probability, tokens))
+}
+
+/// Find the first '*' outside of any brackets or quotes.
+fn find_repeat(expr: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut prev_glyph = ' ';
+    for (index, glyph) in expr.char_indices() {
+        let escaped = prev_glyph == '\\';
+        prev_glyph = glyph;
+        match glyph {
+            '"' if !escaped => quoted = !quoted,
+            _ if quoted => (),
+            '(' | '{' | '[' | '<' => depth += 1,
+            ')' | '}' | ']' | '>' => depth -= 1,
+            '*' if depth == 0 => return Some(index),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Split a double-quoted option value from the front of a string, returning
+/// the unescaped value and the remainder.
+fn split_quoted(expr: &str) -> Option<(String, &str)> {
+    let expr = expr.strip_prefix('"')?;
+    let mut prev_glyph = ' ';
+    for (index, glyph) in expr.char_indices() {
+        if glyph == '"' && prev_glyph != '\\' {
+            let value = expr[..index]
+                .replace("\\\"", "\"")
+                .replace("\\<", "<")
+                .replace("\\>", ">");
+            return Some((value, &expr[index + 1..]));
+        }
+        prev_glyph = glyph;
+    }
+    None
+}
+
+/// Largest number of times a repetition may expand its expression.
+const MAX_REPEAT: usize = 1000;
+
+/// Parse a repetition such as `<good*2-4 sep=", " last=" and " unique>`,
+/// which expands an expression between two and four times, inclusive.
+fn parse_repeat(expr: &str, star: usize, beg: usize, end: usize) -> Result<Token, ParseError> {
+    let target = &expr[..star];
+    if target.is_empty() {
+        return Err(ParseError::InvalidExpression(beg, end));
+    }
+    let token = validate_substitution_expr(target, beg, beg + star)?;
+    let rest = &expr[star + 1..];
+    let count_len = rest
+        .find(|ch: char| !ch.is_ascii_digit() && ch != '-')
+        .unwrap_or(rest.len());
+    let (count, mut options) = rest.split_at(count_len);
+    let (min, max) = match count.find('-') {
+        Some(index) => (count[..index].parse(), count[index + 1..].parse()),
+        None => (count.parse(), count.parse()),
+    };
+    let (min, max) = match (min, max) {
+        (Ok(min), Ok(max)) if min <= max && max <= MAX_REPEAT => (min, max),
+        _ => return Err(ParseError::InvalidRange(beg, end)),
+    };
+    let mut sep = ", ".to_string();
+    let mut last = None;
+    let mut unique = false;
+    loop {
+        let trimmed = options.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+        if trimmed.len() == options.len() {
+            return Err(ParseError::InvalidExpression(beg, end));
+        }
+        options = trimmed;
+        if let Some(rest) = options.strip_prefix("unique") {
+            unique = true;
+            options = rest;
+        } else if let Some(rest) = options.strip_prefix("sep=") {
+            let (value, rest) =
+                split_quoted(rest).ok_or(ParseError::InvalidExpression(beg, end))?;
+            sep = value;
+            options = rest;
+        } else if let Some(rest) = options.strip_prefix("last=") {
+            let (value, rest) =
+                split_quoted(rest).ok_or(ParseError::InvalidExpression(beg, end))?;
+            last = Some(value);
+            options = rest;
+        } else {
+            return Err(ParseError::InvalidExpression(beg, end));
+        }
+    }
+    Ok(Token::Repeat {
+        token: Box::new(token),
+        min,
+        max,
+        sep,
+        last,
+        unique,
+    })
+}
+
+/// Parse a `+key=value` tag assignment.
+fn parse_set_tag(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    let index = match expr.find('=') {
+        Some(index) => index,
+        None => return Err(ParseError::InvalidExpression(beg, end)),
+    };
+    let (key, value) = (expr[..index].trim(), expr[index + 1..].trim());
+    if !is_bare_name(key) {
+        return Err(ParseError::InvalidName(beg, end));
+    }
+    Ok(Token::SetTag(key.to_string(), value.to_string()))
+}
+
+/// Check that a string is a bare name, without any prefix.
+pub(crate) fn is_bare_name(name: &str) -> bool {
+    lazy_static! {
+        static ref BARE_NAME: Regex = Regex::new(r"^[\w0-9_-]+$").unwrap();
+    }
+    BARE_NAME.is_match(name)
+}
+
+/// Check that a string is a Cognate name, which may be qualified by one or
+/// more namespaces, as in `ships::class`.
+pub(crate) fn is_cognate_name(name: &str) -> bool {
+    name.split("::").all(is_bare_name)
+}
+
+/// Parse a single `var:cognate` binding.
+fn parse_binding(expr: &str) -> Option<(String, String)> {
+    let index = expr.find(':')?;
+    let (var, cognate) = (expr[..index].trim(), expr[index + 1..].trim());
+    if is_bare_name(var) && is_cognate_name(cognate) {
+        Some((var.to_string(), cognate.to_string()))
     } else {
-        Err(ParseError::InternalError)
+        None
+    }
+}
+
+/// Parse a `$var:cognate` assignment, which binds `var` and expands to it.
+fn parse_variable(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    match parse_binding(expr) {
+        Some(binding) => {
+            let body = Token::NonTerminal(binding.0.clone());
+            Ok(Token::VariableAssignment(
+                vec![binding],
+                Some(Box::new(body)),
+            ))
+        }
+        None => Err(ParseError::InvalidName(beg, end)),
+    }
+}
+
+/// Parse one or more `[var:cognate]` actions, optionally followed by the
+/// expression they are scoped to.
+fn parse_actions(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+    let mut bindings = vec![];
+    let mut rest = expr;
+    while rest.starts_with('[') {
+        let close = match rest.find(']') {
+            Some(index) => index,
+            None => return Err(ParseError::InvalidExpression(beg, end)),
+        };
+        match parse_binding(&rest[1..close]) {
+            Some(binding) => bindings.push(binding),
+            None => return Err(ParseError::InvalidExpression(beg, end)),
+        }
+        rest = rest[close + 1..].trim_start();
     }
+    let body = if rest.is_empty() {
+        None
+    } else {
+        let offset = beg + expr.len() - rest.len();
+        Some(Box::new(validate_substitution_expr(rest, offset, end)?))
+    };
+    Ok(Token::VariableAssignment(bindings, body))
 }
 
 /// Validate and create a Token from an expression string.
 fn validate_substitution_expr(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
     lazy_static! {
-        static ref VALIDATE_NAME: Regex = Regex::new(r##"^[@!$#]?[\w0-9_-]+$"##).unwrap();
+        static ref VALIDATE_NAME: Regex =
+            Regex::new(r##"^[@!#]?[\w0-9_-]+(::[\w0-9_-]+)*$"##).unwrap();
+    }
+    if let Some((inner, percent)) = split_optional(expr) {
+        return parse_optional(inner, percent, beg, end);
+    }
+    if !expr.starts_with(|ch| "~^+".contains(ch)) {
+        if let Some(star) = find_repeat(expr) {
+            return parse_repeat(expr, star, beg, end);
+        }
     }
     let initial = &expr[0..1];
     match initial {
@@ -87,6 +447,17 @@ fn validate_substitution_expr(expr: &str, beg: usize, end: usize) -> Result<Toke
             }
             parse_cmd_expr(expr, beg, end)
         }
+        "^" => parse_weight(&expr[1..], beg, end),
+        "~" => match expr[1..].trim() {
+            "" => Err(ParseError::InvalidExpression(beg, end)),
+            plural => Ok(Token::PluralForm(plural.to_string())),
+        },
+        "[" => parse_actions(expr, beg, end),
+        "{" => parse_choice(expr, beg, end),
+        "+" => parse_set_tag(&expr[1..], beg, end),
+        "-" if is_bare_name(&expr[1..]) => Ok(Token::ClearTag(expr[1..].to_string())),
+        "-" => Err(ParseError::InvalidName(beg, end)),
+        "$" => parse_variable(&expr[1..], beg, end),
         _ => {
             if !VALIDATE_NAME.is_match(expr) {
                 return Err(ParseError::InvalidName(beg, end));
@@ -95,74 +466,81 @@ fn validate_substitution_expr(expr: &str, beg: usize, end: usize) -> Result<Toke
                 "@" => Ok(Token::Binding(expr[1..].into())),
                 "!" => Ok(Token::StickyNonTerminal(expr[1..].into())),
                 "#" => parse_range(&expr[1..], beg, end),
-                "$" => parse_variable(&expr[1..], beg, end),
                 _ => Ok(Token::NonTerminal(expr.into())),
             }
         }
     }
 }
 
-/// Make a Token::NonTerminal from a string slice.
-fn make_subst(expr: &str, beg: usize, end: usize) -> Result<Token, ParseError> {
+/// Make a Token::NonTerminal from a string slice, reporting errors at
+/// positions offset by `base`.
+fn make_subst(expr: &str, beg: usize, end: usize, base: usize) -> Result<Token, ParseError> {
     if beg == end {
-        return Err(ParseError::ZeroLengthSubst(beg, end));
+        return Err(ParseError::ZeroLengthSubst(base + beg, base + end));
     }
     match expr.get(beg..end) {
-        Some(snip) => validate_substitution_expr(snip, beg, end),
+        Some(snip) => validate_substitution_expr(snip, base + beg, base + end),
         None => Err(ParseError::InternalError),
     }
 }
 
-/// Transform a string into a Vector of Tokens.
-pub fn make_expr(expr: &str) -> Result<Vec<Token>, ParseError> {
-    if expr.is_empty() {
-        return Err(ParseError::EmptyRule);
-    }
+/// Transform a string into a Vector of Tokens, reporting errors at positions
+/// offset by `base`. Substitutions may contain nested substitutions.
+fn make_tokens(expr: &str, base: usize) -> Result<Vec<Token>, ParseError> {
     let mut exprs: Vec<Token> = vec![];
-
-    let mut in_subst = false;
+    let mut depth = 0;
     let mut cbeg = 0;
-
-    let lbrackets = expr.matches('<').count() - expr.matches("\\<").count();
-    let rbrackets = expr.matches('>').count() - expr.matches("\\>").count();
-
-    if lbrackets != rbrackets {
-        return Err(ParseError::UnbalancedBrackets);
-    }
-
     let mut prev_glyph = ' ';
 
-    for (index, glyph) in expr.chars().enumerate() {
+    for (index, glyph) in expr.char_indices() {
+        let escaped = prev_glyph == '\\';
+        prev_glyph = glyph;
         match glyph {
-            '<' => {
-                if in_subst || prev_glyph == '\\' {
-                    continue;
+            '<' if !escaped => {
+                if depth == 0 {
+                    if cbeg != index {
+                        exprs.push(make_literal(expr, cbeg, index));
+                    }
+                    cbeg = index + 1;
                 }
-                if cbeg != index {
-                    exprs.push(make_literal(&expr, cbeg, index));
-                }
-                cbeg = index + 1;
-                in_subst = true;
+                depth += 1;
             }
-            '>' => {
-                if prev_glyph == '\\' {
-                    continue;
+            '>' if !escaped => {
+                if depth == 0 {
+                    return Err(ParseError::UnbalancedBrackets);
+                }
+                depth -= 1;
+                if depth == 0 {
+                    exprs.push(make_subst(expr, cbeg, index, base)?);
+                    cbeg = index + 1;
                 }
-                exprs.push(make_subst(&expr, cbeg, index)?);
-                cbeg = index + 1;
-                in_subst = false;
             }
             _ => (),
         }
-        prev_glyph = glyph;
+    }
+    if depth != 0 {
+        return Err(ParseError::UnbalancedBrackets);
     }
     if cbeg < expr.len() {
-        // check for unterminated subst here
-        exprs.push(make_literal(&expr, cbeg, expr.len()));
+        exprs.push(make_literal(expr, cbeg, expr.len()));
     }
     Ok(exprs)
 }
 
+/// Transform a string into a Vector of Tokens.
+pub fn make_expr(expr: &str) -> Result<Vec<Token>, ParseError> {
+    if expr.is_empty() {
+        return Err(ParseError::EmptyRule);
+    }
+    let lbrackets = expr.matches('<').count() - expr.matches("\\<").count();
+    let rbrackets = expr.matches('>').count() - expr.matches("\\>").count();
+
+    if lbrackets != rbrackets {
+        return Err(ParseError::UnbalancedBrackets);
+    }
+    make_tokens(expr, 0)
+}
+
 pub fn parse(expr: &str) -> Result<Vec<Token>, AnnalsError> {
     match make_expr(expr) {
         Ok(tokens) => Ok(tokens),
@@ -207,10 +585,7 @@ mod test {
             "This is <(cap one)> a complex expression with <!sticky> and <@bind>!",
             [
                 Token::Literal("This is ".into()),
-                Token::Expression(
-                    Command::Capitalize,
-                    Box::new(Token::NonTerminal("one".into()))
-                ),
+                Token::Expression(Command::Capitalize, vec![Token::NonTerminal("one".into())]),
                 Token::Literal(" a complex expression with ".into()),
                 Token::StickyNonTerminal("sticky".into()),
                 Token::Literal(" and ".into()),
@@ -290,26 +665,381 @@ mod test {
             "<(an animal)>",
             [Token::Expression(
                 Command::IndefiniteArticle,
-                Box::new(Token::NonTerminal("animal".to_string()))
+                vec![Token::NonTerminal("animal".to_string())]
             )]
         );
     }
 
     #[test]
     fn test_nested_expression() {
-        let inner = Box::new(Token::Expression(
+        let inner = vec![Token::Expression(
             Command::Capitalize,
-            Box::new(Token::NonTerminal("animal".to_string())),
-        ));
+            vec![Token::NonTerminal("animal".to_string())],
+        )];
         evaluates_to!(
             "<(an (cap animal))>",
             [Token::Expression(Command::IndefiniteArticle, inner)]
         );
     }
 
+    #[test]
+    fn test_template_expression() {
+        assert_eq!(
+            make_expr("The <(title the <!adjective> <noun>)>.").unwrap(),
+            vec![
+                Token::Literal("The ".into()),
+                Token::Expression(
+                    Command::Titlecase,
+                    vec![
+                        Token::Literal("the ".into()),
+                        Token::StickyNonTerminal("adjective".into()),
+                        Token::Literal(" ".into()),
+                        Token::NonTerminal("noun".into()),
+                    ]
+                ),
+                Token::Literal(".".into()),
+            ]
+        );
+        assert_eq!(
+            make_expr("<(cap <(an animal)> and <@pet>)>").unwrap(),
+            vec![Token::Expression(
+                Command::Capitalize,
+                vec![
+                    Token::Expression(
+                        Command::IndefiniteArticle,
+                        vec![Token::NonTerminal("animal".into())]
+                    ),
+                    Token::Literal(" and ".into()),
+                    Token::Binding("pet".into()),
+                ]
+            )]
+        );
+        should_fail_with!("<(cap the <a b>)>", ParseError::InvalidName(11, 14));
+        should_fail_with!("<(cap the <noun>>", ParseError::InvalidExpression(1, 16));
+        should_fail_with!("<(cap the <noun>)", ParseError::UnbalancedBrackets);
+    }
+
+    #[test]
+    fn test_command_arguments() {
+        assert_eq!(
+            make_expr("<(truncate 20 the <noun>)>").unwrap(),
+            vec![Token::Expression(
+                Command::Truncate(20),
+                vec![
+                    Token::Literal("the ".into()),
+                    Token::NonTerminal("noun".into())
+                ]
+            )]
+        );
+        // A lone number is a name, not an argument.
+        assert_eq!(
+            make_expr("<(cap 20)>").unwrap(),
+            vec![Token::Expression(
+                Command::Capitalize,
+                vec![Token::NonTerminal("20".into())]
+            )]
+        );
+        should_fail_with!("<(truncate noun)>", ParseError::InvalidExpression(1, 16));
+        should_fail_with!(
+            "<(truncate 2.5 noun)>",
+            ParseError::InvalidExpression(1, 20)
+        );
+        // Other built-in commands keep leading numbers in their body.
+        assert_eq!(
+            make_expr("<(title 3 men in a <boat>)>").unwrap(),
+            vec![Token::Expression(
+                Command::Titlecase,
+                vec![
+                    Token::Literal("3 men in a ".into()),
+                    Token::NonTerminal("boat".into())
+                ]
+            )]
+        );
+        assert_eq!(
+            make_expr("<(shout infinity <boat>)>").unwrap(),
+            vec![Token::Expression(
+                Command::Custom("shout".into(), vec![], 1, 6),
+                vec![
+                    Token::Literal("infinity ".into()),
+                    Token::NonTerminal("boat".into())
+                ]
+            )]
+        );
+        should_fail_with!("<(cap 3 noun)>", ParseError::InvalidName(6, 13));
+    }
+
+    #[test]
+    fn test_choice() {
+        assert_eq!(
+            make_expr("a <{big|<^3>huge|<size> <(cap x)>}> ship").unwrap(),
+            vec![
+                Token::Literal("a ".into()),
+                Token::Choice(vec![
+                    (vec![Token::Literal("big".into())], 1.0),
+                    (vec![Token::Literal("huge".into())], 3.0),
+                    (
+                        vec![
+                            Token::NonTerminal("size".into()),
+                            Token::Literal(" ".into()),
+                            Token::Expression(
+                                Command::Capitalize,
+                                vec![Token::NonTerminal("x".into())]
+                            ),
+                        ],
+                        1.0
+                    ),
+                ]),
+                Token::Literal(" ship".into()),
+            ]
+        );
+        assert_eq!(
+            make_expr("<{|<{a|b}>}>").unwrap(),
+            vec![Token::Choice(vec![
+                (vec![], 1.0),
+                (
+                    vec![Token::Choice(vec![
+                        (vec![Token::Literal("a".into())], 1.0),
+                        (vec![Token::Literal("b".into())], 1.0),
+                    ])],
+                    1.0
+                ),
+            ])]
+        );
+        should_fail_with!("<{a|b>", ParseError::InvalidExpression(1, 5));
+        should_fail_with!("<{a|<b c>}>", ParseError::InvalidName(5, 8));
+        should_fail_with!("<{<^0>a|<^0>b}>", ParseError::InvalidWeight(1, 14));
+    }
+
+    #[test]
+    fn test_optional() {
+        assert_eq!(
+            make_expr("the <adjective?30> ship").unwrap(),
+            vec![
+                Token::Literal("the ".into()),
+                Token::Optional(0.3, vec![Token::NonTerminal("adjective".into())]),
+                Token::Literal(" ship".into()),
+            ]
+        );
+        assert_eq!(
+            make_expr("<[ <adjective> and <noun>]?>").unwrap(),
+            vec![Token::Optional(
+                0.5,
+                vec![
+                    Token::Literal(" ".into()),
+                    Token::NonTerminal("adjective".into()),
+                    Token::Literal(" and ".into()),
+                    Token::NonTerminal("noun".into()),
+                ]
+            )]
+        );
+        assert_eq!(
+            make_expr("<(cap <what>?)?100>").unwrap(),
+            vec![Token::Optional(
+                1.0,
+                vec![Token::Expression(
+                    Command::Capitalize,
+                    vec![
+                        Token::NonTerminal("what".into()),
+                        Token::Literal("?".into())
+                    ]
+                )]
+            )]
+        );
+        evaluates_to!("<~what?>", [Token::PluralForm("what?".into())]);
+        should_fail_with!("<adjective?101>", ParseError::InvalidExpression(1, 14));
+        should_fail_with!("<adj ective?>", ParseError::InvalidName(1, 11));
+        should_fail_with!("<?>", ParseError::InvalidName(1, 2));
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(
+            make_expr("<good*2-4 sep=\"; \" last=\" and \" unique>").unwrap(),
+            vec![Token::Repeat {
+                token: Box::new(Token::NonTerminal("good".into())),
+                min: 2,
+                max: 4,
+                sep: "; ".into(),
+                last: Some(" and ".into()),
+                unique: true,
+            }]
+        );
+        assert_eq!(
+            make_expr("<(cap good)*3>").unwrap(),
+            vec![Token::Repeat {
+                token: Box::new(Token::Expression(
+                    Command::Capitalize,
+                    vec![Token::NonTerminal("good".into())]
+                )),
+                min: 3,
+                max: 3,
+                sep: ", ".into(),
+                last: None,
+                unique: false,
+            }]
+        );
+        should_fail_with!("<*3>", ParseError::InvalidExpression(1, 3));
+        should_fail_with!("<good*4-2>", ParseError::InvalidRange(1, 9));
+        should_fail_with!("<good*x>", ParseError::InvalidRange(1, 7));
+        should_fail_with!("<good*1001>", ParseError::InvalidRange(1, 10));
+        should_fail_with!(
+            "<good*18446744073709551615>",
+            ParseError::InvalidRange(1, 26)
+        );
+        should_fail_with!(
+            "<good*1-99999999999999999999>",
+            ParseError::InvalidRange(1, 28)
+        );
+        should_fail_with!("<good*2uniq>", ParseError::InvalidExpression(1, 11));
+        should_fail_with!("<good*2 sep=\", >", ParseError::InvalidExpression(1, 15));
+        should_fail_with!("<go od*2>", ParseError::InvalidName(1, 6));
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(
+            make_expr("<+era=new><-weight>a <ship>").unwrap(),
+            vec![
+                Token::SetTag("era".into(), "new".into()),
+                Token::ClearTag("weight".into()),
+                Token::Literal("a ".into()),
+                Token::NonTerminal("ship".into()),
+            ]
+        );
+        assert_eq!(
+            make_expr("<+crew=a*b?>").unwrap(),
+            vec![Token::SetTag("crew".into(), "a*b?".into())]
+        );
+        should_fail_with!("<+era>", ParseError::InvalidExpression(1, 5));
+        should_fail_with!("<+e ra=new>", ParseError::InvalidName(1, 10));
+        should_fail_with!("<-e ra>", ParseError::InvalidName(1, 6));
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        assert_eq!(
+            make_expr("<ships::class> <!ships::name>").unwrap(),
+            vec![
+                Token::NonTerminal("ships::class".into()),
+                Token::Literal(" ".into()),
+                Token::StickyNonTerminal("ships::name".into()),
+            ]
+        );
+        assert_eq!(
+            make_expr("<[hero:crew::captain]>").unwrap(),
+            vec![Token::VariableAssignment(
+                vec![("hero".into(), "crew::captain".into())],
+                None
+            )]
+        );
+        should_fail_with!("<ships:class>", ParseError::InvalidName(1, 12));
+        should_fail_with!("<ships::>", ParseError::InvalidName(1, 8));
+        should_fail_with!(
+            "<[crew::hero:captain]>",
+            ParseError::InvalidExpression(1, 21)
+        );
+    }
+
     #[test]
     fn test_range() {
         evaluates_to!("<#39-100>", [Token::Range(39, 100)]);
+        evaluates_to!("<#5-6>", [Token::Range(5, 6)]);
+        should_fail_with!("<#5-5>", ParseError::InvalidRange(1, 5));
+        should_fail_with!("<#9-1>", ParseError::InvalidRange(1, 5));
+        should_fail_with!("<#1-x>", ParseError::InvalidRange(1, 5));
+    }
+
+    #[test]
+    fn test_weight() {
+        evaluates_to!(
+            "<^2.5>elephant",
+            [Token::Weight(2.5), Token::Literal("elephant".into())]
+        );
+        evaluates_to!("<^3>", [Token::Weight(3.0)]);
+        should_fail_with!("<^>", ParseError::InvalidWeight(1, 2));
+        should_fail_with!("<^heavy>", ParseError::InvalidWeight(1, 7));
+        should_fail_with!("<^-1>", ParseError::InvalidWeight(1, 4));
+    }
+
+    #[test]
+    fn test_plural() {
+        evaluates_to!(
+            "<(plural animal)>",
+            [Token::Expression(
+                Command::Plural,
+                vec![Token::NonTerminal("animal".into())]
+            )]
+        );
+        evaluates_to!(
+            "man of war<~men of war>",
+            [
+                Token::Literal("man of war".into()),
+                Token::PluralForm("men of war".into())
+            ]
+        );
+        should_fail_with!("<~ >", ParseError::InvalidExpression(1, 3));
+    }
+
+    #[test]
+    fn test_custom_command() {
+        evaluates_to!(
+            "<(roman year)>",
+            [Token::Expression(
+                Command::Custom("roman".into(), vec![], 1, 6),
+                vec![Token::NonTerminal("year".into())]
+            )]
+        );
+        evaluates_to!(
+            "<(pad 12 \"-\" year)>",
+            [Token::Expression(
+                Command::Custom("pad".into(), vec!["12".into(), "-".into()], 1, 4),
+                vec![Token::NonTerminal("year".into())]
+            )]
+        );
+        should_fail_with!("<(ro.man year)>", ParseError::UnknownCommand(1, 7));
+    }
+
+    #[test]
+    fn test_actions() {
+        evaluates_to!(
+            "<[hero:name][heroPet:animal]story>",
+            [Token::VariableAssignment(
+                vec![
+                    ("hero".into(), "name".into()),
+                    ("heroPet".into(), "animal".into())
+                ],
+                Some(Box::new(Token::NonTerminal("story".into())))
+            )]
+        );
+        evaluates_to!(
+            "<[hero:name]>",
+            [Token::VariableAssignment(
+                vec![("hero".into(), "name".into())],
+                None
+            )]
+        );
+        evaluates_to!(
+            "<[hero:name](cap story)>",
+            [Token::VariableAssignment(
+                vec![("hero".into(), "name".into())],
+                Some(Box::new(Token::Expression(
+                    Command::Capitalize,
+                    vec![Token::NonTerminal("story".into())]
+                )))
+            )]
+        );
+        evaluates_to!(
+            "<$hero:name>",
+            [Token::VariableAssignment(
+                vec![("hero".into(), "name".into())],
+                Some(Box::new(Token::NonTerminal("hero".into())))
+            )]
+        );
+        should_fail_with!("<[hero]story>", ParseError::InvalidExpression(1, 12));
+        should_fail_with!("<[hero:name story>", ParseError::InvalidExpression(1, 17));
+        should_fail_with!("<[hero:na me]story>", ParseError::InvalidExpression(1, 18));
+        should_fail_with!("<[hero:name]sto ry>", ParseError::InvalidName(12, 18));
+        should_fail_with!("<$hero>", ParseError::InvalidName(1, 6));
     }
 
     #[test]
@@ -0,0 +1,173 @@
+/// A condition on the value of a tag in a [`Context`](../context/struct.Context.html),
+/// written as the value of a [`Group`](../group/struct.Group.html) tag.
+///
+/// | Tag value | Accepts a Context in which the tag...      |
+/// |-----------|--------------------------------------------|
+/// | `old`     | is `old`, or is not set                    |
+/// | `!old`    | is anything but `old`, or is not set       |
+/// | `a\|b`    | is `a` or `b`, or is not set               |
+/// | `!a\|b`   | is neither `a` nor `b`, or is not set      |
+/// | `>=200`   | is a number of at least 200, or is not set |
+/// | `*`       | is set to anything                         |
+/// | `!*`      | is not set                                 |
+/// | `=!old`   | is `!old`, or is not set                   |
+///
+/// The comparisons `<`, `<=`, `>` and `>=` are also available. A value which
+/// does not parse as a predicate, such as `>=many`, is compared for equality.
+///
+/// ```
+/// use annals::predicate::Predicate;
+///
+/// let crew = Predicate::parse(">=200");
+/// assert!(crew.matches(Some("250")));
+/// assert!(!crew.matches(Some("12")));
+/// assert!(crew.matches(None));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// The tag has exactly this value.
+    Equal(String),
+    /// The tag has one of these values.
+    OneOf(Vec<String>),
+    /// The tag does not satisfy the inner Predicate.
+    Not(Box<Predicate>),
+    /// The tag is a number comparing to this one as given.
+    Compare(Comparison, f64),
+    /// The tag is set.
+    Present,
+}
+
+/// A numeric comparison.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Predicate {
+    /// Parse a Group tag value as a Predicate.
+    pub fn parse(value: &str) -> Self {
+        if let Some(literal) = value.strip_prefix('=') {
+            return Predicate::Equal(literal.to_string());
+        }
+        if value == "*" {
+            return Predicate::Present;
+        }
+        if let Some(inner) = value.strip_prefix('!') {
+            return Predicate::Not(Box::new(Predicate::parse(inner)));
+        }
+        let comparisons = [
+            ("<=", Comparison::LessEqual),
+            (">=", Comparison::GreaterEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ];
+        for (op, comparison) in comparisons.iter() {
+            if let Some(number) = value.strip_prefix(op) {
+                if let Ok(number) = number.trim().parse::<f64>() {
+                    return Predicate::Compare(*comparison, number);
+                }
+            }
+        }
+        if value.contains('|') {
+            return Predicate::OneOf(value.split('|').map(str::to_string).collect());
+        }
+        Predicate::Equal(value.to_string())
+    }
+
+    /// Check a tag's value, or `None` if it is not set, against this Predicate.
+    ///
+    /// A tag which is not set satisfies every Predicate except `*`, so that
+    /// Groups are only excluded by tags which the Context actually holds.
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        let value = match value {
+            Some(value) => value,
+            None => return !matches!(self, Predicate::Present),
+        };
+        self.holds(value)
+    }
+
+    /// Check a tag's value against this Predicate.
+    fn holds(&self, value: &str) -> bool {
+        match self {
+            Predicate::Equal(expected) => value == expected,
+            Predicate::OneOf(options) => options.iter().any(|option| option == value),
+            Predicate::Not(inner) => !inner.holds(value),
+            Predicate::Compare(comparison, number) => match value.trim().parse::<f64>() {
+                Ok(value) => match comparison {
+                    Comparison::Less => value < *number,
+                    Comparison::LessEqual => value <= *number,
+                    Comparison::Greater => value > *number,
+                    Comparison::GreaterEqual => value >= *number,
+                },
+                Err(_) => false,
+            },
+            Predicate::Present => true,
+        }
+    }
+
+    /// Get the value which a Context must hold to satisfy this Predicate, if
+    /// it names exactly one.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Predicate::Equal(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Predicate::parse("old"), Predicate::Equal("old".into()));
+        assert_eq!(
+            Predicate::parse("!old"),
+            Predicate::Not(Box::new(Predicate::Equal("old".into())))
+        );
+        assert_eq!(
+            Predicate::parse("light|heavy"),
+            Predicate::OneOf(vec!["light".into(), "heavy".into()])
+        );
+        assert_eq!(
+            Predicate::parse(">= 200"),
+            Predicate::Compare(Comparison::GreaterEqual, 200.0)
+        );
+        assert_eq!(
+            Predicate::parse("<0.5"),
+            Predicate::Compare(Comparison::Less, 0.5)
+        );
+        assert_eq!(Predicate::parse(">many"), Predicate::Equal(">many".into()));
+        assert_eq!(
+            Predicate::parse("!*"),
+            Predicate::Not(Box::new(Predicate::Present))
+        );
+        assert_eq!(Predicate::parse("=!old"), Predicate::Equal("!old".into()));
+    }
+
+    #[test]
+    fn test_matches() {
+        let not_old = Predicate::parse("!old");
+        assert!(not_old.matches(Some("new")));
+        assert!(!not_old.matches(Some("old")));
+        assert!(not_old.matches(None));
+
+        let weight = Predicate::parse("!light|heavy");
+        assert!(weight.matches(Some("medium")));
+        assert!(!weight.matches(Some("heavy")));
+
+        let crew = Predicate::parse("<200");
+        assert!(crew.matches(Some("12")));
+        assert!(!crew.matches(Some("200")));
+        assert!(!crew.matches(Some("lots")));
+
+        assert!(Predicate::parse("*").matches(Some("")));
+        assert!(!Predicate::parse("*").matches(None));
+        assert!(Predicate::parse("!*").matches(None));
+        assert!(!Predicate::parse("!*").matches(Some("x")));
+    }
+}
@@ -2,26 +2,49 @@ use std::fmt;
 use std::slice::Iter;
 
 use crate::error::AnnalsError;
-use crate::parse::{parse, Token};
+use crate::parse::{parse, take_weight, Token};
 
 #[derive(Debug, PartialEq)]
 pub struct Rule {
     literal: String,
     tokens: Vec<Token>,
+    weight: f64,
+    plural: Option<String>,
 }
 
 impl Rule {
     /// Create a rule from a string slice.
+    ///
+    /// Any command which is not built in is parsed as a custom command, and
+    /// is not checked against the Transforms of a Scribe here; check it with
+    /// [`Scribe::validate`](../struct.Scribe.html#method.validate).
     pub fn new(expr: &str) -> Result<Self, AnnalsError> {
-        let literal = expr.into();
-        let tokens = parse(expr)?;
-        Ok(Rule { literal, tokens })
+        Rule::from_string(expr.into())
     }
 
     /// Create a Rule by consuming a String.
+    ///
+    /// A `<^N>` annotation anywhere in the rule sets its selection weight,
+    /// relative to the other Rules in the same Cognate; the default is 1.
+    /// A `<~text>` annotation gives the plural form of the rule's output, for
+    /// use by the `(plural ...)` command.
     pub fn from_string(literal: String) -> Result<Self, AnnalsError> {
-        let tokens = parse(&literal)?;
-        Ok(Rule { literal, tokens })
+        let mut tokens = parse(&literal)?;
+        let weight = take_weight(&mut tokens);
+        let mut plural = None;
+        tokens.retain(|tok| match tok {
+            Token::PluralForm(form) => {
+                plural = Some(form.clone());
+                false
+            }
+            _ => true,
+        });
+        Ok(Rule {
+            literal,
+            tokens,
+            weight,
+            plural,
+        })
     }
 
     /// Get the number of tokens in the Rule.
@@ -35,7 +58,7 @@ impl Rule {
     }
 
     /// Iterate over the tokens in the Rule.
-    pub fn iter(&self) -> Iter<Token> {
+    pub fn iter(&self) -> Iter<'_, Token> {
         self.tokens.iter()
     }
 
@@ -44,6 +67,16 @@ impl Rule {
         &self.literal
     }
 
+    /// Get the selection weight of the Rule.
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Get the plural form of the Rule's output, if one was given.
+    pub fn plural(&self) -> Option<&str> {
+        self.plural.as_deref()
+    }
+
     /// Get the Tokens in the Rule as a slice.
     pub fn tokens(&self) -> &[Token] {
         &self.tokens
@@ -78,16 +111,48 @@ pub mod rule_list {
         D: Deserializer<'de>,
     {
         let literals: Vec<String> = Vec::<String>::deserialize(deserializer)?;
-        match literals.into_iter().map(Rule::from_string).collect() {
-            Ok(rules) => Ok(rules),
-            Err(e) => Err(e).map_err(Error::custom),
-        }
+        literals
+            .into_iter()
+            .map(Rule::from_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::custom)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Rule;
+    use crate::group::Group;
+
+    #[test]
+    fn test_rule_weight() {
+        let rule = Rule::new("<^2.5>elephant").unwrap();
+        assert_eq!(rule.weight(), 2.5);
+        assert_eq!(rule.len(), 1);
+        assert_eq!(rule.literal(), "<^2.5>elephant");
+        assert_eq!(Rule::new("mouse").unwrap().weight(), 1.0);
+    }
+
+    #[test]
+    fn test_rule_plural() {
+        let rule = Rule::new("<^2>goose<~geese>").unwrap();
+        assert_eq!(rule.plural(), Some("geese"));
+        assert_eq!(rule.weight(), 2.0);
+        assert_eq!(rule.len(), 1);
+        assert_eq!(Rule::new("duck").unwrap().plural(), None);
+    }
+
+    #[test]
+    fn test_weight_round_trip() {
+        let yaml = "weight: 0.5\nrules:\n  - <^3>mouse\n  - milk snake\n";
+        let group: Group = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(group.weight, 0.5);
+        assert_eq!(group.rules[0].weight(), 3.0);
+        let group: Group = serde_yaml::from_str(&serde_yaml::to_string(&group).unwrap()).unwrap();
+        assert_eq!(group.weight, 0.5);
+        assert_eq!(group.rules[0].weight(), 3.0);
+        assert_eq!(group.rules[1].weight(), 1.0);
+    }
     #[test]
     fn test_template_valid() {
         macro_rules! good_rule {
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// How a Rule is chosen from the Groups of a Cognate which accept the
+/// current [`Context`](../context/struct.Context.html).
+///
+/// A Group's salience is the number of its tags which the Context holds and
+/// satisfies, so that a Group tagged `{size: big, era: old}` is more specific
+/// than one tagged only `{size: big}`, which in turn is more specific than
+/// an untagged Group.
+///
+/// ```
+/// use annals::{selection::Selection, Scribe};
+///
+/// let mut scribe = Scribe::default();
+/// scribe.set_selection(Selection::Salient);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    /// Choose from the Rules of every accepting Group alike.
+    #[default]
+    Pooled,
+    /// Choose only from the accepting Groups with the highest salience.
+    Salient,
+    /// Choose from every accepting Group, multiplying the weight of each of
+    /// its Rules by one more than its salience.
+    Weighted,
+}
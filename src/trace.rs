@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::group::Group;
+use crate::rule::Rule;
+
+/// A node in the derivation tree of a generated text, recording which
+/// [`Cognate`](../cognate/struct.Cognate.html), [`Group`](../group/struct.Group.html)
+/// and [`Rule`](../rule/struct.Rule.html) produced a piece of the output.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Derivation {
+    /// Name of the expanded Cognate.
+    pub cognate: String,
+    /// Tags of the Group the Rule was chosen from.
+    pub tags: HashMap<String, String>,
+    /// Literal expression of the chosen Rule.
+    pub rule: String,
+    /// Byte range of this expansion within the generated text. Expansions
+    /// bound to a variable rather than output have an empty span at the
+    /// position of the binding.
+    pub span: Range<usize>,
+    /// Expansions made while expanding the Rule, in output order.
+    pub children: Vec<Derivation>,
+}
+
+impl Derivation {
+    /// Get the slice of the generated text produced by this expansion.
+    ///
+    /// Expansions nested inside a command such as `(an ...)` point at the
+    /// transformed text.
+    pub fn text<'a>(&self, output: &'a str) -> &'a str {
+        output.get(self.span.clone()).unwrap_or("")
+    }
+
+    /// Move this node and all of its descendants `delta` bytes to the right,
+    /// clamping every span to `limit`.
+    fn shift(&mut self, delta: usize, limit: usize) {
+        self.span.start = (self.span.start + delta).min(limit);
+        self.span.end = (self.span.end + delta).min(limit);
+        for child in &mut self.children {
+            child.shift(delta, limit);
+        }
+    }
+
+    /// Set the span of this node and all of its descendants to `span`.
+    fn cover(&mut self, span: Range<usize>) {
+        self.span = span.clone();
+        for child in &mut self.children {
+            child.cover(span.clone());
+        }
+    }
+
+    /// Move this node and all of its descendants so that offset `from` lands
+    /// at `to`. Spans must not begin before `from`.
+    fn relocate(&mut self, from: usize, to: usize) {
+        self.span.start = self.span.start - from + to;
+        self.span.end = self.span.end - from + to;
+        for child in &mut self.children {
+            child.relocate(from, to);
+        }
+    }
+}
+
+/// Where a command changes the text of the expansions it transforms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Growth {
+    /// Text is added before the expansions, as with `(an ...)`.
+    Prefix,
+    /// Text is changed in place or added after the expansions, as with
+    /// `(plural ...)`.
+    Suffix,
+    /// Text may be changed anywhere, as with custom transforms.
+    Unknown,
+}
+
+/// Builds a derivation tree during expansion.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Tracer {
+    /// Expansions in progress, innermost last.
+    stack: Vec<Derivation>,
+    /// Completed top-level expansions.
+    roots: Vec<Derivation>,
+    /// Offset in the final output at which the next piece of text will land.
+    pub cursor: usize,
+}
+
+impl Tracer {
+    /// Begin recording the expansion of a Rule.
+    pub fn open(&mut self, cognate: &str, group: &Group, rule: &Rule) {
+        self.stack.push(Derivation {
+            cognate: cognate.to_string(),
+            tags: group.tags.clone(),
+            rule: rule.literal().to_string(),
+            span: self.cursor..self.cursor,
+            children: vec![],
+        });
+    }
+
+    /// Finish recording the innermost expansion, which produced `len` bytes.
+    pub fn close(&mut self, len: usize) {
+        if let Some(mut node) = self.stack.pop() {
+            node.span.end = node.span.start + len;
+            match self.stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => self.roots.push(node),
+            }
+        }
+    }
+
+    /// Record the current position, so that expansions made after this point
+    /// can later be realigned.
+    pub fn mark(&self) -> (usize, usize) {
+        let count = match self.stack.last() {
+            Some(node) => node.children.len(),
+            None => self.roots.len(),
+        };
+        (count, self.cursor)
+    }
+
+    /// Realign expansions recorded since `mark` after their combined text of
+    /// `raw_len` bytes was transformed into `new_len` bytes. Spans are shifted
+    /// past a prefix and clamped to the new text; where the change is unknown
+    /// they cover the whole of it.
+    pub fn realign(
+        &mut self,
+        mark: (usize, usize),
+        raw_len: usize,
+        new_len: usize,
+        growth: Growth,
+    ) {
+        let (count, start) = mark;
+        let delta = match growth {
+            Growth::Prefix => new_len.saturating_sub(raw_len),
+            Growth::Suffix | Growth::Unknown => 0,
+        };
+        let limit = start + new_len;
+        let nodes = match self.stack.last_mut() {
+            Some(node) => &mut node.children,
+            None => &mut self.roots,
+        };
+        for node in nodes.iter_mut().skip(count) {
+            match growth {
+                Growth::Unknown => node.cover(start..limit),
+                _ => node.shift(delta, limit),
+            }
+        }
+    }
+
+    /// Collapse expansions recorded since `mark` to empty spans at the marked
+    /// position, for text which was bound to a variable rather than output.
+    pub fn collapse(&mut self, mark: (usize, usize)) {
+        self.realign(mark, 0, 0, Growth::Suffix);
+        self.cursor = mark.1;
+    }
+
+    /// Move expansions recorded since `mark`, so that text which was expected
+    /// at the marked position lands at `position` instead.
+    pub fn relocate(&mut self, mark: (usize, usize), position: usize) {
+        let (count, start) = mark;
+        let nodes = match self.stack.last_mut() {
+            Some(node) => &mut node.children,
+            None => &mut self.roots,
+        };
+        for node in nodes.iter_mut().skip(count) {
+            node.relocate(start, position);
+        }
+    }
+
+    /// Drop expansions recorded since `mark`, for text which was discarded.
+    pub fn discard(&mut self, mark: (usize, usize)) {
+        let nodes = match self.stack.last_mut() {
+            Some(node) => &mut node.children,
+            None => &mut self.roots,
+        };
+        nodes.truncate(mark.0);
+        self.cursor = mark.1;
+    }
+
+    /// Take the last completed top-level expansion.
+    pub fn finish(&mut self) -> Option<Derivation> {
+        self.roots.pop()
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::cognate::Cognate;
+use crate::error::AnnalsError;
+use crate::parse::{is_bare_name, Command, Token};
+use crate::rule::Rule;
+use crate::Scribe;
+
+/// A part of a grammar which could not be translated to or from tracery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    /// Name of the tracery symbol, or Cognate, containing the problem.
+    pub symbol: String,
+    /// The rule containing the problem, as written in the source grammar.
+    pub rule: String,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} in rule: {}",
+            self.symbol, self.message, self.rule
+        )
+    }
+}
+
+/// A tracery symbol's rules, which may be a single string or a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TraceryRules {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A top-level piece of a tracery rule.
+#[derive(Debug, PartialEq)]
+enum Section {
+    Text(String),
+    Tag(String),
+    Action(String),
+}
+
+/// Split a tracery rule into plain text, `#tag#`s and `[action]`s.
+fn split_sections(rule: &str) -> Result<Vec<Section>, String> {
+    let mut sections = vec![];
+    let mut buf = String::new();
+    let mut depth = 0;
+    let mut in_tag = false;
+    let mut escaped = false;
+    for ch in rule.chars() {
+        let top_level = depth == 0 && !in_tag;
+        if escaped {
+            if !top_level {
+                buf.push('\\');
+            }
+            buf.push(ch);
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '[' => {
+                if top_level {
+                    if !buf.is_empty() {
+                        sections.push(Section::Text(buf.split_off(0)));
+                    }
+                } else {
+                    buf.push(ch);
+                }
+                depth += 1;
+            }
+            ']' => {
+                if depth == 0 {
+                    return Err("unbalanced brackets".to_string());
+                }
+                depth -= 1;
+                if depth == 0 && !in_tag {
+                    sections.push(Section::Action(buf.split_off(0)));
+                } else {
+                    buf.push(ch);
+                }
+            }
+            '#' if depth == 0 => {
+                if in_tag {
+                    sections.push(Section::Tag(buf.split_off(0)));
+                } else if !buf.is_empty() {
+                    sections.push(Section::Text(buf.split_off(0)));
+                }
+                in_tag = !in_tag;
+            }
+            _ => buf.push(ch),
+        }
+    }
+    if in_tag || depth != 0 {
+        return Err("unterminated tag or action".to_string());
+    }
+    if !buf.is_empty() {
+        sections.push(Section::Text(buf));
+    }
+    Ok(sections)
+}
+
+/// Convert a tracery `var:#symbol#` action to an annals `var:symbol` binding.
+fn convert_action(action: &str) -> Result<String, String> {
+    let index = match action.find(':') {
+        Some(index) => index,
+        None => return Err(format!("action [{}] has no value", action)),
+    };
+    let (var, value) = (&action[..index], &action[index + 1..]);
+    if !is_bare_name(var) {
+        return Err(format!("'{}' is not a valid variable name", var));
+    }
+    if value == "POP" {
+        return Err(format!("POP action [{}] is not supported", action));
+    }
+    let symbol = value
+        .strip_prefix('#')
+        .and_then(|value| value.strip_suffix('#'))
+        .filter(|symbol| is_bare_name(symbol));
+    match symbol {
+        Some(symbol) => Ok(format!("{}:{}", var, symbol)),
+        None => Err(format!(
+            "action [{}] must bind a single #symbol# without modifiers",
+            action
+        )),
+    }
+}
+
+/// Split leading `[action]`s from the body of a tag.
+fn split_tag_actions(tag: &str) -> Result<(Vec<&str>, &str), String> {
+    let mut actions = vec![];
+    let mut rest = tag;
+    while rest.starts_with('[') {
+        let mut depth = 0;
+        let mut close = None;
+        for (index, ch) in rest.char_indices() {
+            match ch {
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(index);
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        match close {
+            Some(close) => {
+                actions.push(&rest[1..close]);
+                rest = &rest[close + 1..];
+            }
+            None => return Err("unterminated action".to_string()),
+        }
+    }
+    Ok((actions, rest))
+}
+
+/// Convert a tracery `#[actions]symbol.modifiers#` tag to an annals
+/// expression, pushing a message for each modifier which is dropped.
+fn convert_tag(tag: &str, dropped: &mut Vec<String>) -> Result<String, String> {
+    let (actions, body) = split_tag_actions(tag)?;
+    let mut parts = body.split('.');
+    let symbol = parts.next().unwrap_or("");
+    if !is_bare_name(symbol) {
+        return Err(format!("'{}' is not a valid symbol name", symbol));
+    }
+    let mut expr = symbol.to_string();
+    for modifier in parts {
+        let cmd = match modifier {
+            "capitalize" => "cap",
+            "capitalizeAll" => "title",
+            "a" => "an",
+            "s" => "plural",
+            _ => {
+                dropped.push(format!("modifier .{} is not supported", modifier));
+                continue;
+            }
+        };
+        expr = format!("({} {})", cmd, expr);
+    }
+    let bindings = actions
+        .into_iter()
+        .map(|action| convert_action(action).map(|binding| format!("[{}]", binding)))
+        .collect::<Result<String, String>>()?;
+    Ok(format!("<{}{}>", bindings, expr))
+}
+
+/// Convert a tracery rule to an annals rule. Untranslatable modifiers are
+/// dropped with a message; anything else untranslatable is an error.
+fn convert_rule(rule: &str) -> Result<(String, Vec<String>), String> {
+    if rule.is_empty() {
+        return Err("empty rules are not supported".to_string());
+    }
+    let mut out = String::new();
+    let mut dropped = vec![];
+    for section in split_sections(rule)? {
+        match section {
+            Section::Text(text) => out.push_str(&text.replace('<', "\\<").replace('>', "\\>")),
+            Section::Tag(tag) => out.push_str(&convert_tag(&tag, &mut dropped)?),
+            Section::Action(action) => out.push_str(&format!("<[{}]>", convert_action(&action)?)),
+        }
+    }
+    Ok((out, dropped))
+}
+
+/// Convert a tracery grammar in JSON form into Cognates, one per symbol,
+/// along with warnings for any rules or modifiers which were skipped.
+pub(crate) fn import(data: &str) -> Result<(Vec<Cognate>, Vec<Warning>), AnnalsError> {
+    let symbols: BTreeMap<String, TraceryRules> = serde_json::from_str(data)?;
+    let mut cognates = vec![];
+    let mut warnings = vec![];
+    for (symbol, rules) in symbols {
+        let rules = match rules {
+            TraceryRules::One(rule) => vec![rule],
+            TraceryRules::Many(rules) => rules,
+        };
+        let mut warn = |rule: &str, message: String| {
+            warnings.push(Warning {
+                symbol: symbol.clone(),
+                rule: rule.to_string(),
+                message,
+            })
+        };
+        if !is_bare_name(&symbol) {
+            warn("", "symbol name is not a valid cognate name".to_string());
+            continue;
+        }
+        let mut cognate = Cognate::new(symbol.as_str());
+        let group = cognate.add_group().unwrap();
+        for rule in rules {
+            match convert_rule(&rule) {
+                Ok((converted, dropped)) => {
+                    for message in dropped {
+                        warn(&rule, message);
+                    }
+                    match Rule::from_string(converted) {
+                        Ok(converted) => group.rules.push(converted),
+                        Err(err) => warn(&rule, err.to_string()),
+                    }
+                }
+                Err(message) => warn(&rule, message),
+            }
+        }
+        cognates.push(cognate);
+    }
+    Ok((cognates, warnings))
+}
+
+/// Escape characters with special meaning in tracery rules.
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if let '\\' | '#' | '[' | ']' = ch {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Write a command as it appears in a rule, without its body.
+fn command_name(cmd: &Command) -> String {
+    match cmd {
+        Command::Capitalize => "cap".to_string(),
+        Command::Lowercase => "lowercase".to_string(),
+        Command::Titlecase => "title".to_string(),
+        Command::IndefiniteArticle => "an".to_string(),
+        Command::Plural => "plural".to_string(),
+        Command::Truncate(len) => format!("truncate {}", len),
+        Command::Custom(name, args, ..) => match args.is_empty() {
+            true => name.clone(),
+            false => format!("{} {}", name, args.join(" ")),
+        },
+    }
+}
+
+/// Get the fully qualified name of the Cognate to which `name` refers from a
+/// Cognate in `namespace`. Names which are not Cognates, such as variables,
+/// are left as they are.
+fn qualify<'a>(scribe: &'a Scribe, namespace: &str, name: &'a str) -> &'a str {
+    scribe
+        .resolve(name, namespace)
+        .map_or(name, |view| view.name)
+}
+
+/// Convert an expression built from a name and commands into a tracery
+/// symbol with modifiers, pushing a message for each command which is dropped.
+fn export_tag(
+    token: &Token,
+    scribe: &Scribe,
+    namespace: &str,
+    dropped: &mut Vec<String>,
+) -> Option<String> {
+    match token {
+        Token::NonTerminal(name) => Some(qualify(scribe, namespace, name).to_string()),
+        Token::Expression(cmd, body) => {
+            let tag = match body.as_slice() {
+                [inner] => export_tag(inner, scribe, namespace, dropped)?,
+                _ => return None,
+            };
+            let modifier = match cmd {
+                Command::Capitalize => "capitalize",
+                Command::Titlecase => "capitalizeAll",
+                Command::IndefiniteArticle => "a",
+                Command::Plural => "s",
+                Command::Lowercase => {
+                    dropped.push("command (lowercase ...) has no tracery equivalent".to_string());
+                    return Some(tag);
+                }
+                Command::Truncate(len) => {
+                    dropped.push(format!(
+                        "command (truncate {} ...) has no tracery equivalent",
+                        len
+                    ));
+                    return Some(tag);
+                }
+                Command::Custom(name, args, ..) => {
+                    dropped.push(format!(
+                        "command ({} ...) was exported as a modifier, which must be added to tracery",
+                        name
+                    ));
+                    if !args.is_empty() {
+                        return Some(format!("{}.{}({})", tag, name, args.join(",")));
+                    }
+                    name
+                }
+            };
+            Some(format!("{}.{}", tag, modifier))
+        }
+        _ => None,
+    }
+}
+
+/// Convert a Token from a Rule of a Cognate in `namespace` to tracery, pushing
+/// a message for each feature which has no tracery equivalent.
+fn export_token(
+    token: &Token,
+    scribe: &Scribe,
+    namespace: &str,
+    out: &mut String,
+    dropped: &mut Vec<String>,
+) {
+    match token {
+        Token::Literal(text) => out.push_str(&escape_text(text)),
+        Token::NonTerminal(name) => {
+            out.push_str(&format!("#{}#", qualify(scribe, namespace, name)))
+        }
+        Token::StickyNonTerminal(name) => out.push_str(&format!(
+            "[{0}:#{0}#]#{0}#",
+            qualify(scribe, namespace, name)
+        )),
+        Token::Binding(name) => {
+            dropped.push(format!(
+                "binding <@{}> has no tracery equivalent, and was exported as a symbol",
+                name
+            ));
+            out.push_str(&format!("#{}#", name));
+        }
+        Token::Expression(cmd, body) => match export_tag(token, scribe, namespace, dropped) {
+            Some(tag) => out.push_str(&format!("#{}#", tag)),
+            None => {
+                let mut text = String::new();
+                let mut inner = vec![];
+                for token in body {
+                    export_token(token, scribe, namespace, &mut text, &mut inner);
+                }
+                dropped.push(format!(
+                    "command ({} ...) applied to \"{}\" has no tracery equivalent, and was dropped",
+                    command_name(cmd),
+                    text
+                ));
+                dropped.extend(inner);
+                out.push_str(&text);
+            }
+        },
+        Token::Range(lower, upper) => {
+            dropped.push(format!(
+                "range <#{}-{}> has no tracery equivalent, and was exported as its lower bound",
+                lower, upper
+            ));
+            out.push_str(&lower.to_string());
+        }
+        Token::VariableAssignment(bindings, body) => {
+            let actions = bindings
+                .iter()
+                .map(|(var, cognate)| {
+                    format!("[{}:#{}#]", var, qualify(scribe, namespace, cognate))
+                })
+                .collect::<String>();
+            match body
+                .as_ref()
+                .map(|body| (body, export_tag(body, scribe, namespace, dropped)))
+            {
+                Some((_, Some(tag))) => out.push_str(&format!("#{}{}#", actions, tag)),
+                Some((body, None)) => {
+                    dropped.push(
+                        "bindings scoped to a complex expression were exported unscoped"
+                            .to_string(),
+                    );
+                    out.push_str(&actions);
+                    export_token(body, scribe, namespace, out, dropped);
+                }
+                None => out.push_str(&actions),
+            }
+        }
+        Token::Choice(choices) => {
+            dropped.push(
+                "inline choice has no tracery equivalent, and was exported as its first alternative"
+                    .to_string(),
+            );
+            if let Some((tokens, _)) = choices.first() {
+                for token in tokens {
+                    export_token(token, scribe, namespace, out, dropped);
+                }
+            }
+        }
+        Token::Repeat { token, .. } => {
+            dropped.push(
+                "repetition has no tracery equivalent, and was exported as a single expansion"
+                    .to_string(),
+            );
+            export_token(token, scribe, namespace, out, dropped);
+        }
+        Token::Optional(_, tokens) => {
+            dropped.push(
+                "optional segment has no tracery equivalent, and was exported as always present"
+                    .to_string(),
+            );
+            for token in tokens {
+                export_token(token, scribe, namespace, out, dropped);
+            }
+        }
+        Token::SetTag(key, value) => {
+            dropped.push(format!(
+                "tag assignment <+{}={}> has no tracery equivalent",
+                key, value
+            ));
+        }
+        Token::ClearTag(key) => {
+            dropped.push(format!("tag removal <-{}> has no tracery equivalent", key));
+        }
+        Token::Weight(_) | Token::PluralForm(_) => (),
+    }
+}
+
+/// Flatten the Cognates in a Scribe into tracery symbols, along with warnings
+/// for any features which have no tracery equivalent.
+pub(crate) fn export(scribe: &Scribe) -> (BTreeMap<String, Vec<String>>, Vec<Warning>) {
+    let mut symbols = BTreeMap::new();
+    let mut warnings = vec![];
+    for cognate in scribe.views() {
+        let mut warn = |rule: &str, message: String| {
+            warnings.push(Warning {
+                symbol: cognate.name.to_string(),
+                rule: rule.to_string(),
+                message,
+            })
+        };
+        let mut rules = vec![];
+        for group in &cognate.groups {
+            if !group.tags.is_empty() {
+                let tags = group
+                    .tags
+                    .iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<_>>();
+                warn(
+                    "",
+                    format!(
+                        "group tags {{{}}} have no tracery equivalent, and were dropped",
+                        tags.join(", ")
+                    ),
+                );
+            }
+            if (group.weight - 1.0).abs() > f64::EPSILON {
+                warn("", "group weights have no tracery equivalent".to_string());
+            }
+            for rule in &group.rules {
+                let mut out = String::new();
+                let mut dropped = vec![];
+                if (rule.weight() - 1.0).abs() > f64::EPSILON {
+                    dropped.push("rule weights have no tracery equivalent".to_string());
+                }
+                for token in rule.tokens() {
+                    export_token(token, scribe, cognate.namespace(), &mut out, &mut dropped);
+                }
+                for message in dropped {
+                    warn(rule.literal(), message);
+                }
+                rules.push(out);
+            }
+        }
+        symbols.insert(cognate.name.to_string(), rules);
+    }
+    (symbols, warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Scribe;
+
+    #[test]
+    fn test_convert_rule() {
+        macro_rules! converts_to {
+            ($tracery: expr, $annals: expr) => {
+                assert_eq!(convert_rule($tracery).unwrap().0, $annals);
+            };
+        }
+        converts_to!("plain <text>", "plain \\<text\\>");
+        converts_to!("#hero# is #mood#.", "<hero> is <mood>.");
+        converts_to!("#animal.a.capitalize#!", "<(cap (an animal))>!");
+        converts_to!("#animal.capitalizeAll#", "<(title animal)>");
+        converts_to!("#animal.s#", "<(plural animal)>");
+        converts_to!(
+            "#[hero:#name#][heroPet:#animal#]story#",
+            "<[hero:name][heroPet:animal]story>"
+        );
+        converts_to!("[hero:#name#]#hero# left", "<[hero:name]><hero> left");
+        converts_to!("a \\#hashtag", "a #hashtag");
+
+        let (converted, dropped) = convert_rule("#animal.ed#").unwrap();
+        assert_eq!(converted, "<animal>");
+        assert_eq!(dropped, vec!["modifier .ed is not supported"]);
+
+        assert!(convert_rule("").is_err());
+        assert!(convert_rule("#unterminated").is_err());
+        assert!(convert_rule("[hero:POP]").is_err());
+        assert!(convert_rule("[mood:happy]#story#").is_err());
+        assert!(convert_rule("#bad name#").is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let mut scr = Scribe::default();
+        let warnings = scr
+            .load_tracery_json_str(include_str!("../texts/tracery.json"))
+            .unwrap();
+        assert_eq!(warnings, vec![]);
+        let text = scr.gen("origin").unwrap();
+        let hero = text.split(' ').next().unwrap();
+        assert!(text.contains(&format!(". {} was never", hero)));
+
+        let warnings = scr
+            .load_tracery_json_str(r##"{"a": ["#b.ed#", "[x:y]#b#"], "b": "ok", "bad name": []}"##)
+            .unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[0].symbol, "a");
+        assert_eq!(warnings[0].rule, "#b.ed#");
+        assert_eq!(scr.gen("a").unwrap(), "ok");
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let source = include_str!("../texts/tracery.json");
+        let mut scr = Scribe::default();
+        scr.load_tracery_json_str(source).unwrap();
+        let (json, warnings) = scr.to_tracery_json().unwrap();
+        assert_eq!(warnings, vec![]);
+        let exported: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let original: serde_json::Value = serde_json::from_str(source).unwrap();
+        assert_eq!(exported, original);
+    }
+
+    #[test]
+    fn test_export_namespaces() {
+        let mut scr = Scribe::default();
+        scr.load_project("texts/project/fleet/main.yml").unwrap();
+        let (symbols, warnings) = export(&scr);
+        assert_eq!(warnings, vec![]);
+        assert_eq!(symbols["navy::ship"], vec!["HMS #navy::name#"]);
+        assert_eq!(
+            symbols["merchant::ship"],
+            vec!["SS #merchant::name#, master #captain#"]
+        );
+        assert_eq!(symbols["root"], vec!["#navy::ship# and #merchant::ship#"]);
+    }
+
+    #[test]
+    fn test_export_warnings() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: root
+  groups:
+    - rules: ["<@speaker> saw <#1-9> <!animal>s, <(low animal)> # [sic]"]
+    - rules: ["<(title the <animal>)>"]
+- name: animal
+  groups:
+    - tags: {size: big}
+      rules: ["<^2>whale"]
+"#,
+        )
+        .unwrap();
+        let (symbols, warnings) = export(&scr);
+        assert_eq!(
+            symbols["root"],
+            vec![
+                "#speaker# saw 1 [animal:#animal#]#animal#s, #animal# \\# \\[sic\\]",
+                "the #animal#"
+            ]
+        );
+        assert_eq!(symbols["animal"], vec!["whale"]);
+        let messages = warnings
+            .iter()
+            .map(|w| (w.symbol.as_str(), w.message.split(' ').next().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                ("animal", "group"),
+                ("animal", "rule"),
+                ("root", "binding"),
+                ("root", "range"),
+                ("root", "command"),
+                ("root", "command"),
+            ]
+        );
+        assert_eq!(
+            warnings[0].message,
+            "group tags {size: big} have no tracery equivalent, and were dropped"
+        );
+        assert_eq!(
+            warnings[5].message,
+            "command (title ...) applied to \"the #animal#\" has no tracery equivalent, and was dropped"
+        );
+    }
+}
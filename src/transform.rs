@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A text transformation applied by a command expression such as
+/// `<(shout name)>`, registered with
+/// [`Scribe::register_transform`](../struct.Scribe.html#method.register_transform).
+///
+/// Closures taking a `&str` and returning a `String` implement this trait.
+///
+/// ```
+/// use annals::Scribe;
+///
+/// let mut scribe = Scribe::default();
+/// scribe.register_transform("shout", |text: &str| format!("{}!", text.to_uppercase()));
+/// scribe
+///     .load_cognates_str("[{name: cry, groups: [{rules: [ahoy]}]}]")
+///     .unwrap();
+/// assert_eq!(scribe.expand("<(shout cry)>").unwrap(), "AHOY!");
+/// ```
+pub trait Transform: Send + Sync {
+    /// Transform the expanded text of the command's body.
+    fn apply(&self, text: &str) -> String;
+
+    /// Transform the expanded text of the command's body, given the literal
+    /// arguments written before it, as in `<(pad 12 "." name)>`. By default
+    /// the arguments are ignored.
+    fn apply_with_args(&self, _args: &[String], text: &str) -> String {
+        self.apply(text)
+    }
+}
+
+impl<F> Transform for F
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn apply(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
+/// Named Transforms available to command expressions.
+#[derive(Clone, Default)]
+pub(crate) struct Transforms {
+    map: HashMap<String, Arc<dyn Transform>>,
+}
+
+impl Transforms {
+    /// Register a Transform, replacing any with the same name.
+    pub fn insert(&mut self, name: &str, transform: Arc<dyn Transform>) {
+        self.map.insert(name.to_string(), transform);
+    }
+
+    /// Get the Transform registered under a name, if any.
+    pub fn get(&self, name: &str) -> Option<&dyn Transform> {
+        self.map.get(name).map(|transform| transform.as_ref())
+    }
+
+    /// Check if a Transform is registered under a name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+}
+
+impl fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.map.keys()).finish()
+    }
+}
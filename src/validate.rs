@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use crate::parse::{Command, Token};
+use crate::Scribe;
+
+/// A problem found by [`Scribe::validate`](../struct.Scribe.html#method.validate).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// The root passed to `validate` is not a Cognate in the Scribe.
+    UnknownRoot { name: String },
+    /// A Rule refers to a name which is neither a Cognate nor a variable.
+    UnknownCognate {
+        cognate: String,
+        rule: String,
+        name: String,
+    },
+    /// A Rule uses a command which is neither built in nor registered, at
+    /// bytes `start..end` of the Rule.
+    UnknownCommand {
+        cognate: String,
+        rule: String,
+        name: String,
+        start: usize,
+        end: usize,
+    },
+    /// A Cognate has no Rules to select from.
+    EmptyCognate { name: String },
+    /// A Cognate cannot be reached from the root.
+    Unreachable { name: String },
+    /// Every Rule of a Cognate leads back to itself, so expansion never ends.
+    NonTerminating { name: String },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Diagnostic::*;
+        match self {
+            UnknownRoot { name } => write!(f, "Unknown root cognate: {}", name),
+            UnknownCognate {
+                cognate,
+                rule,
+                name,
+            } => write!(
+                f,
+                "Unknown cognate {} referenced by {}: {}",
+                name, cognate, rule
+            ),
+            UnknownCommand {
+                cognate,
+                rule,
+                name,
+                start,
+                end,
+            } => write!(
+                f,
+                "Unknown command {} at {}-{} in {}: {}",
+                name, start, end, cognate, rule
+            ),
+            EmptyCognate { name } => write!(f, "No rules in cognate: {}", name),
+            Unreachable { name } => write!(f, "Cognate is unreachable: {}", name),
+            NonTerminating { name } => write!(f, "Cognate never terminates: {}", name),
+        }
+    }
+}
+
+/// The names a Rule must expand in order to finish: every name in `all`,
+/// and for each inline choice in `any`, every name required by at least one
+/// of its alternatives.
+#[derive(Debug, Default)]
+struct Requirement<'a> {
+    all: Vec<&'a str>,
+    any: Vec<Vec<Requirement<'a>>>,
+}
+
+impl<'a> Requirement<'a> {
+    /// Replace every name with the result of `resolve`.
+    fn resolve(&mut self, resolve: &dyn Fn(&'a str) -> &'a str) {
+        for name in &mut self.all {
+            *name = resolve(name);
+        }
+        for alternative in self.any.iter_mut().flatten() {
+            alternative.resolve(resolve);
+        }
+    }
+
+    /// Check whether this Requirement is met, given which names can finish.
+    fn met(&self, done: &dyn Fn(&str) -> bool) -> bool {
+        self.all.iter().all(|name| done(name))
+            && self
+                .any
+                .iter()
+                .all(|alternatives| alternatives.iter().any(|alt| alt.met(done)))
+    }
+}
+
+/// The text of a Rule, the names it references and the names it needs to
+/// finish.
+type RuleRefs<'a> = (&'a str, Vec<&'a str>, Requirement<'a>);
+
+/// A custom command used by a Rule, with the span of its name.
+type CommandUse<'a> = (&'a str, usize, usize);
+
+/// Collect the names referenced by a Token, any variables it binds and any
+/// custom commands it uses. If the Token is always expanded, the names it
+/// needs to finish are added to `req`.
+fn walk<'a>(
+    token: &'a Token,
+    mut req: Option<&mut Requirement<'a>>,
+    refs: &mut Vec<&'a str>,
+    vars: &mut HashSet<&'a str>,
+    cmds: &mut Vec<CommandUse<'a>>,
+) {
+    match token {
+        Token::NonTerminal(name) | Token::StickyNonTerminal(name) => {
+            refs.push(name);
+            if let Some(req) = req {
+                req.all.push(name);
+            }
+        }
+        Token::VariableAssignment(bindings, body) => {
+            for (var, name) in bindings {
+                vars.insert(var);
+                refs.push(name);
+                if let Some(req) = req.as_deref_mut() {
+                    req.all.push(name);
+                }
+            }
+            if let Some(body) = body {
+                walk(body, req, refs, vars, cmds);
+            }
+        }
+        Token::Expression(cmd, body) => {
+            if let Command::Custom(name, _, start, end) = cmd {
+                cmds.push((name, *start, *end));
+            }
+            for token in body {
+                walk(token, req.as_deref_mut(), refs, vars, cmds);
+            }
+        }
+        Token::Choice(choices) => {
+            let mut alternatives = vec![];
+            for (tokens, _) in choices {
+                let mut alternative = Requirement::default();
+                for token in tokens {
+                    walk(token, Some(&mut alternative), refs, vars, cmds);
+                }
+                alternatives.push(alternative);
+            }
+            if let Some(req) = req {
+                req.any.push(alternatives);
+            }
+        }
+        Token::Repeat { token, min, .. } => {
+            let req = req.filter(|_| *min > 0);
+            walk(token, req, refs, vars, cmds)
+        }
+        Token::Optional(probability, tokens) => {
+            let mut req = req.filter(|_| *probability >= 1.0);
+            for token in tokens {
+                walk(token, req.as_deref_mut(), refs, vars, cmds);
+            }
+        }
+        Token::Literal(_)
+        | Token::Binding(_)
+        | Token::Range(..)
+        | Token::Weight(_)
+        | Token::PluralForm(_)
+        | Token::SetTag(..)
+        | Token::ClearTag(_) => (),
+    }
+}
+
+/// Check every Rule in the Scribe, returning all problems found.
+///
+/// Rules are only checked for unregistered commands when loaded from data,
+/// so those added in code, such as by
+/// [`Group::add_rules`](../group/struct.Group.html#method.add_rules), are
+/// checked here.
+///
+/// Tags are ignored: a Rule counts as able to terminate if the Cognates it
+/// always refers to can, whatever Context they are expanded in. An inline
+/// choice can terminate if any of its alternatives can, while names in an
+/// optional segment which may be skipped are assumed not to be expanded.
+/// Names bound only from code, via
+/// [`Context::bind`](../context/struct.Context.html#method.bind), are reported
+/// as unknown.
+pub(crate) fn check(scribe: &Scribe, root: Option<&str>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut vars = HashSet::new();
+    // Cognate name -> references made by each of its Rules.
+    let mut graph: BTreeMap<&str, Vec<RuleRefs>> = BTreeMap::new();
+    for cognate in scribe
+        .names()
+        .into_iter()
+        .filter_map(|name| scribe.view(name))
+    {
+        let rules = graph.entry(cognate.name).or_default();
+        for rule in cognate.groups.iter().flat_map(|grp| grp.rules.iter()) {
+            let mut refs = vec![];
+            let mut req = Requirement::default();
+            let mut cmds = vec![];
+            for token in rule.tokens() {
+                walk(token, Some(&mut req), &mut refs, &mut vars, &mut cmds);
+            }
+            for (name, start, end) in cmds {
+                if !scribe.transforms.contains(name) {
+                    diagnostics.push(Diagnostic::UnknownCommand {
+                        cognate: cognate.name.to_string(),
+                        rule: rule.literal().to_string(),
+                        name: name.to_string(),
+                        start,
+                        end,
+                    });
+                }
+            }
+            // Refer to Cognates by their full names, as resolved from this one.
+            let resolve = |name| match scribe.resolve(name, cognate.namespace()) {
+                Some(target) => target.name,
+                None => name,
+            };
+            let refs = refs.into_iter().map(resolve).collect();
+            req.resolve(&resolve);
+            rules.push((rule.literal(), refs, req));
+        }
+    }
+
+    for (name, rules) in &graph {
+        if rules.is_empty() {
+            diagnostics.push(Diagnostic::EmptyCognate {
+                name: name.to_string(),
+            });
+        }
+        for (rule, refs, _) in rules {
+            let unknown = refs
+                .iter()
+                .filter(|name| !graph.contains_key(*name) && !vars.contains(*name))
+                .collect::<BTreeSet<_>>();
+            for unknown_name in unknown {
+                diagnostics.push(Diagnostic::UnknownCognate {
+                    cognate: name.to_string(),
+                    rule: rule.to_string(),
+                    name: unknown_name.to_string(),
+                });
+            }
+        }
+    }
+
+    if let Some(root) = root {
+        if graph.contains_key(root) {
+            let mut seen = BTreeSet::new();
+            let mut queue = vec![root];
+            while let Some(name) = queue.pop() {
+                if !seen.insert(name) {
+                    continue;
+                }
+                if let Some(rules) = graph.get(name) {
+                    queue.extend(rules.iter().flat_map(|(_, refs, _)| refs.iter().copied()));
+                }
+            }
+            for name in graph.keys().filter(|name| !seen.contains(*name)) {
+                diagnostics.push(Diagnostic::Unreachable {
+                    name: name.to_string(),
+                });
+            }
+        } else {
+            diagnostics.push(Diagnostic::UnknownRoot {
+                name: root.to_string(),
+            });
+        }
+    }
+
+    // A Cognate terminates if any of its Rules needs only Cognates which
+    // terminate; unknown names and empty Cognates are already reported, so
+    // treat them as leaves.
+    let mut terminating: HashSet<&str> = HashSet::new();
+    loop {
+        let before = terminating.len();
+        for (name, rules) in &graph {
+            if terminating.contains(name) {
+                continue;
+            }
+            let done = |r: &str| terminating.contains(r) || graph.get(r).is_none_or(Vec::is_empty);
+            let ends = rules.iter().any(|(_, _, req)| req.met(&done));
+            if ends {
+                terminating.insert(name);
+            }
+        }
+        if terminating.len() == before {
+            break;
+        }
+    }
+    for (name, rules) in &graph {
+        if !rules.is_empty() && !terminating.contains(name) {
+            diagnostics.push(Diagnostic::NonTerminating {
+                name: name.to_string(),
+            });
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_clean() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(include_str!("../texts/hms.yml"))
+            .unwrap();
+        assert_eq!(scr.validate(Some("root")), vec![]);
+    }
+
+    #[test]
+    fn test_validate_reports_all() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: root
+  groups:
+    - rules: ["<a> <missing>", "<(cap b)>"]
+- name: a
+  groups:
+    - rules: ["<a>", "<b> <a>"]
+- name: b
+  groups:
+    - rules: ["<a>"]
+- name: empty
+  groups: []
+- name: orphan
+  groups:
+    - rules: ["fine"]
+- name: chain
+  groups:
+    - rules: ["<{end|<chain> and on}>"]
+"#,
+        )
+        .unwrap();
+        let diagnostics = scr.validate(Some("root"));
+        let expected = vec![
+            Diagnostic::EmptyCognate {
+                name: "empty".into(),
+            },
+            Diagnostic::UnknownCognate {
+                cognate: "root".into(),
+                rule: "<a> <missing>".into(),
+                name: "missing".into(),
+            },
+            Diagnostic::Unreachable {
+                name: "chain".into(),
+            },
+            Diagnostic::Unreachable {
+                name: "empty".into(),
+            },
+            Diagnostic::Unreachable {
+                name: "orphan".into(),
+            },
+            Diagnostic::NonTerminating { name: "a".into() },
+            Diagnostic::NonTerminating { name: "b".into() },
+            Diagnostic::NonTerminating {
+                name: "root".into(),
+            },
+        ];
+        assert_eq!(diagnostics, expected);
+        assert_eq!(
+            scr.validate(Some("nowhere"))[2],
+            Diagnostic::UnknownRoot {
+                name: "nowhere".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_commands() {
+        let mut scr = Scribe::default();
+        scr.register_transform("shout", |text: &str| text.to_uppercase());
+        scr.cognate("x").group_from_rules(&["x"]).unwrap();
+        scr.cognate("root")
+            .group_from_rules(&["<(shout <(cap (bogus x))>)>"])
+            .unwrap();
+        assert_eq!(
+            scr.validate(Some("root")),
+            vec![Diagnostic::UnknownCommand {
+                cognate: "root".into(),
+                rule: "<(shout <(cap (bogus x))>)>".into(),
+                name: "bogus".into(),
+                start: 14,
+                end: 19,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_choices() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            r#"
+- name: a
+  groups:
+    - rules: ["<{<a>|<a> again}>"]
+- name: b
+  groups:
+    - rules: ["<{<a>|<b> again}>", "<b?100>"]
+- name: c
+  groups:
+    - rules: ["<{<a>|<b>|<{x|<c>}>}>", "<c?50>"]
+"#,
+        )
+        .unwrap();
+        let expected = vec![
+            Diagnostic::NonTerminating { name: "a".into() },
+            Diagnostic::NonTerminating { name: "b".into() },
+        ];
+        assert_eq!(scr.validate(None), expected);
+    }
+
+    #[test]
+    fn test_validate_empty_reference() {
+        let mut scr = Scribe::default();
+        scr.load_cognates_str(
+            "[{name: root, groups: [{rules: [<empty>]}]}, {name: empty, groups: []}]",
+        )
+        .unwrap();
+        assert_eq!(
+            scr.validate(Some("root")),
+            vec![Diagnostic::EmptyCognate {
+                name: "empty".into()
+            }]
+        );
+    }
+}